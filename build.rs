@@ -1,7 +1,19 @@
+use std::fmt::Write as _;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::{env, fs};
 
+// shared with the crate, so rustfmt leaves it to the settings in src:
+#[path = "src/palette_import.rs"]
+#[allow(dead_code)]
+#[rustfmt::skip]
+mod palette_import;
+
+use palette_import::ImportedStop;
+
+/// The most stops or colors a single imported palette can have.
+const MAX_IMPORTED_ENTRIES: usize = 256;
+
 fn main() {
     // Put the linker script somewhere the linker can find it
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
@@ -12,4 +24,84 @@ fn main() {
         .write_all(include_bytes!("memory.x"))
         .unwrap();
     println!("cargo:rerun-if-changed=memory.x");
+
+    fs::write(
+        out_dir.join("imported_palettes.rs"),
+        import_palettes(Path::new("palettes")),
+    )
+    .unwrap();
+    println!("cargo:rerun-if-changed=palettes");
+}
+
+/// Turns every palette file in `dir` into a const, and lists them all in IMPORTED_PALETTES so
+/// colors.rs can add them to PALETTE_ARRAY. FastLED gradients come from `.h` files, WLED
+/// palettes from `.json` files and GIMP palettes from `.gpl` files.
+fn import_palettes(dir: &Path) -> String {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries.map(|entry| entry.unwrap().path()).collect(),
+        Err(_) => Vec::new(),
+    };
+    // read_dir order isn't stable, but PALETTE_ARRAY indices should be:
+    paths.sort();
+
+    let mut source = String::new();
+    let mut registry = Vec::new();
+    for path in paths {
+        let text = fs::read_to_string(&path).unwrap();
+        let stem = path.file_stem().unwrap().to_str().unwrap();
+        let fail = |e| panic!("couldn't import palette {}: {:?}", path.display(), e);
+        let mut stops = [ImportedStop {
+            index: 0,
+            rgb: [0; 3],
+        }; MAX_IMPORTED_ENTRIES];
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("h") => {
+                for (name, body) in palette_import::FastLedPalettes::new(&text) {
+                    let count =
+                        palette_import::parse_fastled_source(body, &mut stops).unwrap_or_else(fail);
+                    let name = const_name("G_", name);
+                    palette_import::write_gradient(&mut source, &name, &stops[..count]).unwrap();
+                    registry.push(format!("Palette::Gradient({})", name));
+                }
+            }
+            Some("json") => {
+                let count = palette_import::parse_wled(&text, &mut stops).unwrap_or_else(fail);
+                let name = const_name("G_", stem);
+                palette_import::write_gradient(&mut source, &name, &stops[..count]).unwrap();
+                registry.push(format!("Palette::Gradient({})", name));
+            }
+            Some("gpl") => {
+                let mut colors = [[0; 3]; MAX_IMPORTED_ENTRIES];
+                let count = palette_import::parse_gpl(&text, &mut colors).unwrap_or_else(fail);
+                let name = const_name("R_", stem);
+                palette_import::write_rainbow(&mut source, &name, &colors[..count]).unwrap();
+                registry.push(format!("Palette::Rainbow({})", name));
+            }
+            _ => {}
+        }
+    }
+
+    writeln!(
+        source,
+        "pub const NUM_IMPORTED_PALETTES: usize = {};",
+        registry.len()
+    )
+    .unwrap();
+    writeln!(
+        source,
+        "pub const IMPORTED_PALETTES: [Palette; NUM_IMPORTED_PALETTES] = ["
+    )
+    .unwrap();
+    for entry in registry {
+        writeln!(source, "    {},", entry).unwrap();
+    }
+    writeln!(source, "];").unwrap();
+    source
+}
+
+fn const_name(prefix: &str, name: &str) -> String {
+    let mut const_name = String::new();
+    palette_import::write_const_name(&mut const_name, prefix, name).unwrap();
+    const_name
 }
@@ -32,23 +32,13 @@ pub const NUM_LEDS_NORTH_WALL: usize = 35;
 pub const NUM_LEDS_WEST_WALL: usize = 49;
 
 // individual strips:
-pub const CLOSET_STRIP: strip::PhysicalStrip = strip::PhysicalStrip {
-    led_count: 34,
-    reversed: false,
-    color_order: strip::ColorOrder::BRG,
-    strip_timings: strip::StripTimings::WS2812_ADAFRUIT,
-};
-pub const WINDOW_STRIP: strip::PhysicalStrip = strip::PhysicalStrip {
-    led_count: 74,
-    reversed: false,
-    color_order: strip::ColorOrder::BRG,
-    strip_timings: strip::StripTimings::WS2812_ADAFRUIT,
-};
+pub const CLOSET_STRIP: strip::PhysicalStrip =
+    strip::PhysicalStrip::new(34, strip::ColorOrder::BRG, strip::StripTimings::WS2812_ADAFRUIT);
+pub const WINDOW_STRIP: strip::PhysicalStrip =
+    strip::PhysicalStrip::new(74, strip::ColorOrder::BRG, strip::StripTimings::WS2812_ADAFRUIT);
 pub const DOOR_STRIP: strip::PhysicalStrip = strip::PhysicalStrip {
-    led_count: 59,
     reversed: true,
-    color_order: strip::ColorOrder::BRG,
-    strip_timings: strip::StripTimings::WS2812_ADAFRUIT,
+    ..strip::PhysicalStrip::new(59, strip::ColorOrder::BRG, strip::StripTimings::WS2812_ADAFRUIT)
 };
 
 pub const NUM_STRIPS: usize = 3;
@@ -57,6 +47,7 @@ pub const ALL_STRIPS: [strip::PhysicalStrip; NUM_STRIPS] = [CLOSET_STRIP, WINDOW
 
 // calculate the total number of LEDs from the above values:
 pub const NUM_LEDS: usize = crate::get_total_num_leds(&ALL_STRIPS);
+pub const NUM_BYTES: usize = crate::get_total_num_bytes(&ALL_STRIPS);
 
 #[riscv_rt::entry]
 fn main() -> ! {
@@ -90,9 +81,10 @@ fn main() -> ! {
     writeln!(serial, "Debug Serial Initialized...\r").ok();
 
     // set aside memory for a logical strip
-    let mut memory_buffer = [0; NUM_LEDS * 3];
-    let mut color_buffer: [c::Color; NUM_LEDS] = [c::Color::default(); NUM_LEDS];
-    let office_strip = strip::LogicalStrip::new(&mut memory_buffer, &mut color_buffer, &ALL_STRIPS);
+    let mut strips = ALL_STRIPS;
+    let mut memory_buffer = [0; NUM_BYTES];
+    let mut color_buffer: [c::Color16; NUM_LEDS] = [c::Color16::default(); NUM_LEDS];
+    let office_strip = strip::LogicalStrip::new(&mut memory_buffer, &mut color_buffer, &mut strips);
 
     // The order of pins here needs to match the array of strips passed into LogicalStrip::new()
     let mut pins = [
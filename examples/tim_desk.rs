@@ -21,29 +21,31 @@ use hal::{gpio::*, pac};
 use panic_write::PanicHandler;
 
 // individual strips:
-pub const STRIP_ONE: strip::PhysicalStrip = strip::PhysicalStrip {
-    led_count: 4,
-    reversed: false,
-    color_order: strip::ColorOrder::GRB,
-    strip_timings: strip::StripTimings::WS2812_ADAFRUIT,
-};
+pub const STRIP_ONE: strip::PhysicalStrip = strip::PhysicalStrip::new(
+    4,
+    strip::ColorOrder::GRB,
+    strip::StripTimings::WS2812_ADAFRUIT,
+);
 pub const STRIP_TWO: strip::PhysicalStrip = strip::PhysicalStrip {
-    led_count: 4,
     reversed: true,
-    color_order: strip::ColorOrder::GRB,
-    strip_timings: strip::StripTimings::WS2812_ADAFRUIT,
-};
-pub const STRIP_THREE: strip::PhysicalStrip = strip::PhysicalStrip {
-    led_count: 4,
-    reversed: false,
-    color_order: strip::ColorOrder::GRB,
-    strip_timings: strip::StripTimings::WS2812_ADAFRUIT,
+    ..strip::PhysicalStrip::new(
+        4,
+        strip::ColorOrder::GRB,
+        strip::StripTimings::WS2812_ADAFRUIT,
+    )
 };
+pub const STRIP_THREE: strip::PhysicalStrip = strip::PhysicalStrip::new(
+    4,
+    strip::ColorOrder::GRB,
+    strip::StripTimings::WS2812_ADAFRUIT,
+);
 pub const STRIP_FOUR: strip::PhysicalStrip = strip::PhysicalStrip {
-    led_count: 4,
     reversed: true,
-    color_order: strip::ColorOrder::GRB,
-    strip_timings: strip::StripTimings::WS2812_ADAFRUIT,
+    ..strip::PhysicalStrip::new(
+        4,
+        strip::ColorOrder::GRB,
+        strip::StripTimings::WS2812_ADAFRUIT,
+    )
 };
 
 pub const NUM_STRIPS: usize = 4;
@@ -53,6 +55,7 @@ pub const ALL_STRIPS: [strip::PhysicalStrip; NUM_STRIPS] =
 
 // calculate the total number of LEDs from the above values:
 pub const NUM_LEDS: usize = crate::get_total_num_leds(&ALL_STRIPS);
+pub const NUM_BYTES: usize = crate::get_total_num_bytes(&ALL_STRIPS);
 
 #[riscv_rt::entry]
 fn main() -> ! {
@@ -93,9 +96,10 @@ fn main() -> ! {
         &mut gpio.pin4.into_pull_down_output(),
     ];
 
-    let mut memory_buffer = [0; NUM_LEDS * 3];
-    let mut color_buffer: [c::Color; NUM_LEDS] = [c::Color::default(); NUM_LEDS];
-    let strip = strip::LogicalStrip::new(&mut memory_buffer, &mut color_buffer, &ALL_STRIPS);
+    let mut strips = ALL_STRIPS;
+    let mut memory_buffer = [0; NUM_BYTES];
+    let mut color_buffer: [c::Color16; NUM_LEDS] = [c::Color16::default(); NUM_LEDS];
+    let strip = strip::LogicalStrip::new(&mut memory_buffer, &mut color_buffer, &mut strips);
 
     let mut hc = HardwareController::new(&mut pins, timer_ch0);
 
@@ -1,6 +1,8 @@
-use crate::colors::Color;
-use crate::{background, foreground, trigger};
+use crate::colors::{Color16, LayerBlend};
+use crate::utility::{self, AnimationRng};
+use crate::{background, default_animations, foreground, trigger};
 use embedded_time::rate::*;
+use rand::{RngCore, SeedableRng};
 
 /// Adjust MAX_NUM_* consts depending on RAM requirements:
 pub(crate) const MAX_NUM_ACTIVE_TRIGGERS: usize = 100;
@@ -18,7 +20,7 @@ pub enum Direction {
 }
 
 /// Denotes the main types of animations, e.g. Foreground, Background, or Trigger:
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum AnimationType {
     Background,
     Foreground,
@@ -34,62 +36,328 @@ pub struct AnimationParameters<'a> {
     pub trigger: trigger::GlobalParameters<'a>,
 }
 
-/// This struct contains all the fixed parameters of an animation, as well as the state of the
-/// foreground, background, and active trigger animations. It is updated by the LightingController
-/// that it is attached to at the LightingController's frame rate based on the parameters provided.
-/// To make a new animation,
-pub struct Animation<'a, const N_LED: usize> {
+/// The parameters for a single layer in an animation's stack of layers. Layers are drawn in
+/// order, each one blended onto the ones before it with its own blend mode and opacity.
+pub enum LayerParameters<'a> {
+    Background(background::Parameters<'a>),
+    Foreground(foreground::Parameters<'a>),
+    Trigger(trigger::GlobalParameters<'a>),
+}
+
+impl<'a> From<AnimationParameters<'a>> for [LayerParameters<'a>; 3] {
+    fn from(parameters: AnimationParameters<'a>) -> Self {
+        [
+            LayerParameters::Background(parameters.bg),
+            LayerParameters::Foreground(parameters.fg),
+            LayerParameters::Trigger(parameters.trigger),
+        ]
+    }
+}
+
+/// Limits where a layer shows through. Where the mask is fully open the layer is blended in with
+/// its own opacity, where it's closed the layer doesn't show at all, and in between it shows
+/// partly. LED indices are positions in the animation's segment, not the logical strip.
+#[derive(Default, Copy, Clone)]
+pub enum Mask<'a> {
+    /// The layer shows on every LED.
+    #[default]
+    None,
+
+    /// The layer shows on every `every`th LED, starting at the LED at `start`.
+    EveryNth { every: usize, start: usize },
+
+    /// The layer shows on the LEDs from `start` up to, but not including, `end`.
+    Range { start: usize, end: usize },
+
+    /// How much the layer shows, from 0 to 255, spread evenly from the first LED to the last
+    /// like the colors of a rainbow, and blended linearly in between. `&[0, 255, 0]` fades the
+    /// layer out toward both ends of the segment.
+    Gradient(&'a [u8]),
+
+    /// The layer shows where the layer at this index in the stack is bright. The layers are
+    /// drawn in order, so a layer below this one, or this layer itself, is followed as drawn this
+    /// frame, while a layer above it is followed as drawn last frame, and keeps the mask closed
+    /// on the first frame. Every `Luminance` mask in a stack has to follow the same layer.
+    Luminance(usize),
+}
+
+impl<'a> Mask<'a> {
+    /// How much a layer shows at `index` in a segment of `led_count` LEDs, from 0 to 255.
+    /// `source` is the luminance of each LED of the layer a `Luminance` mask follows.
+    pub fn alpha(&self, index: usize, led_count: usize, source: Option<&[u8]>) -> u8 {
+        let open = |is_open: bool| match is_open {
+            true => u8::MAX,
+            false => 0,
+        };
+        match *self {
+            Mask::None => u8::MAX,
+            Mask::EveryNth { every, start } => {
+                let every = every.max(1);
+                open(index >= start && (index - start).is_multiple_of(every))
+            }
+            Mask::Range { start, end } => open((start..end).contains(&index)),
+            Mask::Gradient(stops) => gradient_alpha(stops, index, led_count),
+            Mask::Luminance(_) => match source {
+                Some(source) => source[index],
+                None => u8::MAX,
+            },
+        }
+    }
+}
+
+fn gradient_alpha(stops: &[u8], index: usize, led_count: usize) -> u8 {
+    match stops {
+        [] => return u8::MAX,
+        [only] => return *only,
+        _ if index + 1 >= led_count => return stops[stops.len() - 1],
+        _ => {}
+    }
+    // the position between stops in 8.8 fixed point:
+    let position = index * (stops.len() - 1) * 256 / (led_count - 1);
+    let (stop, fraction) = (position >> 8, (position & 0xFF) as i32);
+    let (start, end) = (stops[stop] as i32, stops[stop + 1] as i32);
+    (start + (end - start) * fraction / 256) as u8
+}
+
+// the perceived brightness of a color from 0 to 255, using the Rec. 709 weights:
+fn luminance(color: Color16) -> u8 {
+    let weighted = color.r as u32 * 54 + color.g as u32 * 183 + color.b as u32 * 19;
+    (weighted >> 16) as u8
+}
+
+type Triggers<'a> = trigger::TriggerCollection<'a, MAX_NUM_ACTIVE_TRIGGERS>;
+
+/// Why a stack of layers couldn't be made into an animation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StackError {
+    /// The stack had more than one trigger layer, but an animation only has room for one set of
+    /// active triggers.
+    TooManyTriggerLayers,
+}
+
+/// The state of a single layer in an animation's stack of layers. The active triggers take far
+/// more RAM than the other kinds of layer, and there's no heap to box them into, so they are
+/// kept in a slot of their own in the `Animation` and the trigger layer only marks where in the
+/// stack they are drawn. MAX_NUM_ACTIVE_TRIGGERS sets how much RAM that slot takes.
+pub enum Layer<'a> {
+    Background(background::Background<'a>),
+    Foreground(foreground::Foreground<'a>),
+    Trigger,
+}
+
+impl<'a> Layer<'a> {
+    // The background and trigger layers each take a seed from `seeder`, in stack order. A
+    // trigger layer's state goes into `triggers`, the stack having been checked to have at most
+    // one of them:
+    fn new(
+        init: &LayerParameters<'a>,
+        frame_rate: Hertz,
+        seeder: &mut AnimationRng,
+        triggers: &mut Option<Triggers<'a>>,
+    ) -> Self {
+        match init {
+            LayerParameters::Background(bg) => {
+                Layer::Background(background::Background::new(bg, frame_rate, seeder.next_u64()))
+            }
+            LayerParameters::Foreground(fg) => {
+                Layer::Foreground(foreground::Foreground::new(fg, frame_rate))
+            }
+            LayerParameters::Trigger(trigger) => {
+                *triggers = Some(Triggers::new(trigger, frame_rate, seeder.next_u64()));
+                Layer::Trigger
+            }
+        }
+    }
+
+    pub fn animation_type(&self) -> AnimationType {
+        match self {
+            Layer::Background(_) => AnimationType::Background,
+            Layer::Foreground(_) => AnimationType::Foreground,
+            Layer::Trigger => AnimationType::Trigger,
+        }
+    }
+
+    // The methods below take the animation's triggers for the trigger layer to use:
+
+    fn blend(&self, triggers: &Triggers<'a>) -> LayerBlend {
+        match self {
+            Layer::Background(bg) => bg.blend,
+            Layer::Foreground(fg) => fg.blend,
+            Layer::Trigger => triggers.blend,
+        }
+    }
+
+    fn mask(&self, triggers: &Triggers<'a>) -> Mask<'a> {
+        match self {
+            Layer::Background(bg) => bg.mask,
+            Layer::Foreground(fg) => fg.mask,
+            Layer::Trigger => triggers.mask,
+        }
+    }
+
+    fn update(&mut self, triggers: &mut Triggers<'a>, segment: &mut [Color16]) {
+        match self {
+            Layer::Background(bg) => bg.update(segment),
+            Layer::Foreground(fg) => fg.update(segment),
+            Layer::Trigger => triggers.update(segment),
+        }
+    }
+
+    fn set_offset(&mut self, offset: u16) {
+        match self {
+            Layer::Background(bg) => bg.offset = offset,
+            Layer::Foreground(fg) => fg.offset = offset,
+            // Triggers don't use offsets, so do nothing until they need to.
+            Layer::Trigger => {}
+        }
+    }
+
+    // Background and foreground layers take any trigger as a nudge to their next color or
+    // offset, and trigger layers start a new trigger animation for every mode that isn't meant
+    // for one of the other two:
+    fn trigger(
+        &mut self,
+        triggers: &mut Triggers<'a>,
+        params: &trigger::Parameters,
+        frame_rate: Hertz,
+    ) {
+        match (self, params.mode) {
+            (_, trigger::Mode::NoTrigger) => {}
+            (Layer::Background(bg), _) => bg.has_been_triggered = true,
+            (Layer::Foreground(fg), _) => fg.has_been_triggered = true,
+            (Layer::Trigger, trigger::Mode::Background | trigger::Mode::Foreground) => {}
+            (Layer::Trigger, _) => triggers.add_trigger(params, frame_rate),
+        }
+    }
+
+    fn set_seed(&mut self, triggers: &mut Triggers<'a>, seeder: &mut AnimationRng) {
+        match self {
+            Layer::Background(bg) => bg.set_seed(seeder.next_u64()),
+            Layer::Foreground(_) => {}
+            Layer::Trigger => triggers.set_seed(seeder.next_u64()),
+        }
+    }
+}
+
+/// This struct contains all the fixed parameters of an animation, as well as the state of each
+/// layer in its stack. By default that's a background, a foreground, and the active trigger
+/// animations, but it can be any number of background and foreground layers in any order, with
+/// at most one trigger layer among them. It is updated by the LightingController that it is
+/// attached to at the LightingController's frame rate based on the parameters provided.
+pub struct Animation<'a, const N_LED: usize, const N_LAYER: usize = 3> {
     translation_array: [usize; N_LED],
-    segment: [Color; N_LED],
-    fg_state: foreground::Foreground<'a>,
-    bg_state: background::Background<'a>,
-    triggers: trigger::TriggerCollection<'a, MAX_NUM_ACTIVE_TRIGGERS>,
+    segment: [Color16; N_LED],
+    // each layer is drawn here in turn before being blended onto the segment:
+    scratch: [Color16; N_LED],
+    // the luminance of the layer that the stack's `Mask::Luminance` masks follow, if any:
+    mask_source: Option<usize>,
+    luminance: [u8; N_LED],
+    layers: [Layer<'a>; N_LAYER],
+    triggers: Triggers<'a>,
 }
 
 pub trait Animatable<'a> {
     fn update(&mut self);
+    /// Sets the offset of every layer of type `a_type`.
     fn set_offset(&mut self, a_type: AnimationType, offset: u16);
+    /// Sets the offset of the layer at `layer_index` in the stack.
+    fn set_layer_offset(&mut self, layer_index: usize, offset: u16);
+    /// Sends a trigger to every layer its mode is meant for: `Mode::Background` to the
+    /// background layers, `Mode::Foreground` to the foreground layers, and the rest to the
+    /// trigger layers.
     fn trigger(&mut self, params: &trigger::Parameters, frame_rate: Hertz);
-    fn segment(&self) -> &[Color];
+    /// Sends a trigger to the layer at `layer_index` in the stack only.
+    fn trigger_layer(
+        &mut self,
+        layer_index: usize,
+        params: &trigger::Parameters,
+        frame_rate: Hertz,
+    );
+    fn set_seed(&mut self, seed: u64);
+    fn segment(&self) -> &[Color16];
     fn translation_array(&self) -> &[usize];
 }
 
-impl<'a, const N_LED: usize> Animatable<'a> for Animation<'a, N_LED> {
+impl<'a, const N_LED: usize, const N_LAYER: usize> Animatable<'a>
+    for Animation<'a, N_LED, N_LAYER>
+{
     fn update(&mut self) {
-        // Update all three states
-        self.bg_state.update(&mut self.segment);
-        self.fg_state.update(&mut self.segment);
-        self.triggers.update(&mut self.segment);
-    }
+        // Update every layer, blending each one onto the ones below it. Nothing is below the
+        // first layer, so it's blended onto off:
+        let segment = &mut self.segment;
+        segment.fill(Color16::default());
+        for (index, state) in self.layers.iter_mut().enumerate() {
+            let (blend, mask) = (state.blend(&self.triggers), state.mask(&self.triggers));
 
-    fn set_offset(&mut self, a_type: AnimationType, offset: u16) {
-        match a_type {
-            AnimationType::Background => {
-                self.bg_state.offset = offset;
+            // each layer starts out as its blend mode's neutral colors, so the LEDs it doesn't
+            // draw on leave the segment as it was:
+            let buffer = &mut self.scratch;
+            for (led, below) in buffer.iter_mut().zip(segment.iter()) {
+                *led = blend.mode.neutral(*below);
             }
-            AnimationType::Foreground => {
-                self.fg_state.offset = offset;
+            state.update(&mut self.triggers, buffer);
+
+            if self.mask_source == Some(index) {
+                for (level, led) in self.luminance.iter_mut().zip(buffer.iter()) {
+                    *level = luminance(*led);
+                }
             }
-            AnimationType::Trigger => {
-                // Triggers don't use offsets, so do nothing until they need to.
+            let source = match mask {
+                Mask::Luminance(_) => Some(&self.luminance[..]),
+                _ => None,
+            };
+            for (led_index, (below, led)) in segment.iter_mut().zip(buffer.iter()).enumerate() {
+                let alpha = mask.alpha(led_index, N_LED, source) as u32;
+                let opacity = (blend.opacity as u32 * alpha + 127) / 255;
+                *below = LayerBlend::new(blend.mode, opacity as u8).apply(*below, *led);
             }
         }
     }
 
-    fn trigger(&mut self, params: &trigger::Parameters, frame_rate: Hertz) {
-        match params.mode {
-            trigger::Mode::NoTrigger => {}
-            trigger::Mode::Background => {
-                self.bg_state.has_been_triggered = true;
+    fn set_offset(&mut self, a_type: AnimationType, offset: u16) {
+        for layer in self.layers.iter_mut() {
+            if layer.animation_type() == a_type {
+                layer.set_offset(offset);
             }
-            trigger::Mode::Foreground => {
-                self.fg_state.has_been_triggered = true;
+        }
+    }
+
+    fn set_layer_offset(&mut self, layer_index: usize, offset: u16) {
+        self.layers[layer_index].set_offset(offset);
+    }
+
+    fn trigger(&mut self, params: &trigger::Parameters, frame_rate: Hertz) {
+        let a_type = match params.mode {
+            trigger::Mode::Background => AnimationType::Background,
+            trigger::Mode::Foreground => AnimationType::Foreground,
+            _ => AnimationType::Trigger,
+        };
+        for layer in self.layers.iter_mut() {
+            if layer.animation_type() == a_type {
+                layer.trigger(&mut self.triggers, params, frame_rate);
             }
-            _ => self.triggers.add_trigger(params, frame_rate),
         }
     }
 
-    fn segment(&self) -> &[Color] {
+    fn trigger_layer(
+        &mut self,
+        layer_index: usize,
+        params: &trigger::Parameters,
+        frame_rate: Hertz,
+    ) {
+        self.layers[layer_index].trigger(&mut self.triggers, params, frame_rate);
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        // The layers each get their own generator so that adding a trigger doesn't shift the
+        // sequence the background sees:
+        let mut seeder = AnimationRng::seed_from_u64(seed);
+        for layer in self.layers.iter_mut() {
+            layer.set_seed(&mut self.triggers, &mut seeder);
+        }
+    }
+
+    fn segment(&self) -> &[Color16] {
         &self.segment[..]
     }
 
@@ -104,11 +372,95 @@ impl<'a, const N_LED: usize> Animation<'a, N_LED> {
         translation_array: [usize; N_LED],
         frame_rate: Hertz,
     ) -> Self {
-        let segment = [Color::default(); N_LED];
-        let fg_state = foreground::Foreground::new(&parameters.fg, frame_rate);
-        let bg_state = background::Background::new(&parameters.bg, frame_rate);
-        let triggers = trigger::TriggerCollection::new(&parameters.trigger, frame_rate);
+        Self::new_with_seed(parameters, translation_array, frame_rate, utility::default_seed())
+    }
+
+    /// Makes a new animation whose random effects will play out the same way every time for
+    /// the same `seed`.
+    pub fn new_with_seed(
+        parameters: AnimationParameters<'a>,
+        translation_array: [usize; N_LED],
+        frame_rate: Hertz,
+        seed: u64,
+    ) -> Self {
+        // these always have exactly one trigger layer:
+        Self::from_stack(parameters.into(), translation_array, frame_rate, seed)
+    }
+}
+
+impl<'a, const N_LED: usize, const N_LAYER: usize> Animation<'a, N_LED, N_LAYER> {
+    /// Makes a new animation from a stack of layers, drawn from first to last. Returns an error
+    /// if the stack has more than one trigger layer, and panics if it has a `Mask::Luminance`
+    /// that follows a layer that isn't in the stack or a different layer than another one does.
+    pub fn new_stack(
+        layers: [LayerParameters<'a>; N_LAYER],
+        translation_array: [usize; N_LED],
+        frame_rate: Hertz,
+    ) -> Result<Self, StackError> {
+        Self::new_stack_with_seed(layers, translation_array, frame_rate, utility::default_seed())
+    }
+
+    /// Makes a new animation from a stack of layers whose random effects will play out the same
+    /// way every time for the same `seed`.
+    pub fn new_stack_with_seed(
+        layers: [LayerParameters<'a>; N_LAYER],
+        translation_array: [usize; N_LED],
+        frame_rate: Hertz,
+        seed: u64,
+    ) -> Result<Self, StackError> {
+        let trigger_layers = layers
+            .iter()
+            .filter(|init| matches!(init, LayerParameters::Trigger(_)))
+            .count();
+        if trigger_layers > 1 {
+            return Err(StackError::TooManyTriggerLayers);
+        }
+        Ok(Self::from_stack(layers, translation_array, frame_rate, seed))
+    }
+
+    fn from_stack(
+        layers: [LayerParameters<'a>; N_LAYER],
+        translation_array: [usize; N_LED],
+        frame_rate: Hertz,
+        seed: u64,
+    ) -> Self {
+        let mut mask_source = None;
+        for init in layers.iter() {
+            let mask = match init {
+                LayerParameters::Background(bg) => bg.mask,
+                LayerParameters::Foreground(fg) => fg.mask,
+                LayerParameters::Trigger(trigger) => trigger.mask,
+            };
+            if let Mask::Luminance(source) = mask {
+                assert!(source < N_LAYER, "Luminance masks have to follow a layer in the stack");
+                assert!(
+                    mask_source.unwrap_or(source) == source,
+                    "Luminance masks have to follow the same layer"
+                );
+                mask_source = Some(source);
+            }
+        }
+
+        let mut seeder = AnimationRng::seed_from_u64(seed);
+        let mut triggers = None;
+        let layers = layers.map(|init| Layer::new(&init, frame_rate, &mut seeder, &mut triggers));
+        // without a trigger layer the triggers are never drawn, so they're just left off:
+        let triggers = triggers.unwrap_or_else(|| {
+            Triggers::new(&default_animations::TRIGGER_OFF, frame_rate, seeder.next_u64())
+        });
+
+        Animation {
+            translation_array,
+            segment: [Color16::default(); N_LED],
+            scratch: [Color16::default(); N_LED],
+            mask_source,
+            luminance: [0; N_LED],
+            layers,
+            triggers,
+        }
+    }
 
-        Animation { translation_array, segment, fg_state, bg_state, triggers }
+    pub fn layers(&self) -> &[Layer<'a>] {
+        &self.layers[..]
     }
 }
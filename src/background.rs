@@ -1,11 +1,12 @@
-use crate::animations::{Direction, MAX_OFFSET};
-use crate::colors::{self, Color, Rainbow};
+use crate::animations::{Direction, Mask, MAX_OFFSET};
+use crate::colors::{self, Color16, Interpolation, LayerBlend, Palette};
 use crate::utility::{
-    self, convert_ns_to_frames, get_random_offset, FadeRainbow, MarchingRainbow,
+    self, convert_ns_to_frames, get_random_offset, AnimationRng, FadeRainbow, MarchingRainbow,
     MarchingRainbowMut, Progression, StatefulRainbow,
 };
 use embedded_time::rate::Hertz;
-type BgUpdater = fn(&mut Background, &mut [Color]);
+use rand::SeedableRng;
+type BgUpdater = fn(&mut Background, &mut [Color16]);
 
 /// Background Modes are rendered onto the animation LEDs first before any Foreground or Trigger
 /// animations. The other types of animation will overwrite any pixel data from the background that
@@ -34,6 +35,13 @@ pub enum Mode {
     /// When externally triggered, it moves to a random offset.
     FillRainbowRotate,
 
+    /// This shows a single color temperature on all the leds, drifting from `from_kelvin` to
+    /// `to_kelvin` and back again once every `duration_ns`. With a long duration this gives slow
+    /// changes like lighting getting warmer through the evening. `Direction::Negative` starts
+    /// from `to_kelvin` instead, and `Direction::Stopped` holds it at `from_kelvin`. The rainbow
+    /// is not used, and external triggers do nothing.
+    TemperatureDrift { from_kelvin: u32, to_kelvin: u32 },
+
     /// This will use the function provided with the enum to do the update
     Custom(BgUpdater),
 }
@@ -46,24 +54,25 @@ impl Mode {
             Mode::SolidFade => Some(solid_fade),
             Mode::FillRainbow => Some(fill_rainbow),
             Mode::FillRainbowRotate => Some(fill_rainbow_rotate),
+            Mode::TemperatureDrift { .. } => Some(temperature_drift),
             Mode::Custom(u) => Some(u),
         }
     }
 }
 
 /// Sets all LEDs to off
-fn no_background(bg: &mut Background, segment: &mut [Color]) {
-    bg.fill_solid(colors::C_OFF, segment);
+fn no_background(bg: &mut Background, segment: &mut [Color16]) {
+    bg.fill_solid(colors::C_OFF.into(), segment);
 }
 
 /// Sets all LEDs to the current rainbow color. Note that in this mode the color will only
 /// change when an external trigger of type `Background` is received.
-fn solid(bg: &mut Background, segment: &mut [Color]) {
+fn solid(bg: &mut Background, segment: &mut [Color16]) {
     handle_solid_trigger(bg);
-    bg.fill_solid(bg.current_rainbow_color(), segment);
+    bg.fill_solid(bg.current_rainbow_color().into(), segment);
 }
 
-fn solid_fade(bg: &mut Background, segment: &mut [Color]) {
+fn solid_fade(bg: &mut Background, segment: &mut [Color16]) {
     handle_solid_trigger(bg);
     for led in segment {
         *led = bg.calculate_fade_color();
@@ -71,12 +80,12 @@ fn solid_fade(bg: &mut Background, segment: &mut [Color]) {
 }
 
 /// Fills the rainbow based on whatever value the offset is currently set to:
-fn fill_rainbow(bg: &mut Background, segment: &mut [Color]) {
+fn fill_rainbow(bg: &mut Background, segment: &mut [Color16]) {
     handle_rainbow_trigger(bg);
     bg.fill_rainbow(bg.offset, segment);
 }
 
-fn fill_rainbow_rotate(bg: &mut Background, segment: &mut [Color]) {
+fn fill_rainbow_rotate(bg: &mut Background, segment: &mut [Color16]) {
     handle_rainbow_trigger(bg);
 
     // This mode will take the value that the offset is set to and then adjust based on the
@@ -87,10 +96,29 @@ fn fill_rainbow_rotate(bg: &mut Background, segment: &mut [Color]) {
     bg.fill_rainbow(color_start_offset, segment);
 }
 
+fn temperature_drift(bg: &mut Background, segment: &mut [Color16]) {
+    let (from, to) = bg.kelvin_range;
+    let (from, to) = match bg.direction {
+        Direction::Positive => (from, to),
+        Direction::Negative => (to, from),
+        Direction::Stopped => (from, from),
+    };
+
+    // the first half of the frames drift one way and the second half drift back:
+    let (current, total) = (bg.frames.get_current() as u32, bg.frames.total as u32);
+    let half = total / 2;
+    let factor = match current <= half {
+        true => current,
+        false => total - current,
+    };
+    let kelvin = colors::kelvin_lerp(from, to, factor, half);
+    bg.fill_solid(colors::kelvin_to_color(kelvin).into(), segment);
+}
+
 /// Sets the background to a random offset then resets the trigger
 fn handle_rainbow_trigger(bg: &mut Background) {
     if bg.has_been_triggered {
-        bg.offset = get_random_offset();
+        bg.offset = get_random_offset(&mut bg.rng);
         bg.reset_trigger();
     }
 }
@@ -107,11 +135,14 @@ fn handle_solid_trigger(bg: &mut Background) {
 /// aspects of the animation can be derived from these parameters.
 pub struct Parameters<'a> {
     pub mode: Mode,
-    pub rainbow: Rainbow<'a>,
+    pub rainbow: Palette<'a>,
     pub direction: Direction,
     pub is_rainbow_forward: bool,
     pub duration_ns: u64,
     pub subdivisions: usize,
+    pub interpolation: Interpolation,
+    pub blend: LayerBlend,
+    pub mask: Mask<'a>,
 }
 
 pub struct Background<'a> {
@@ -119,30 +150,47 @@ pub struct Background<'a> {
     pub offset: u16,
     pub frames: Progression,
     pub has_been_triggered: bool,
+    pub rng: AnimationRng,
 
     // parameters
     pub rainbow: StatefulRainbow<'a>,
+    pub blend: LayerBlend,
+    pub mask: Mask<'a>,
     direction: Direction,
     subdivisions: usize,
+    interpolation: Interpolation,
+    kelvin_range: (u32, u32),
     updater: Option<BgUpdater>,
 }
 
 impl<'a> Background<'a> {
-    pub fn new(init: &Parameters<'a>, frame_rate: Hertz) -> Self {
+    pub fn new(init: &Parameters<'a>, frame_rate: Hertz, seed: u64) -> Self {
         let frame_count = convert_ns_to_frames(init.duration_ns, frame_rate);
 
         Self {
             offset: 0,
             frames: Progression::new(frame_count),
             has_been_triggered: false,
+            rng: AnimationRng::seed_from_u64(seed),
             rainbow: StatefulRainbow::new(init.rainbow, init.is_rainbow_forward),
+            blend: init.blend,
+            mask: init.mask,
             direction: init.direction,
             subdivisions: init.subdivisions,
+            interpolation: init.interpolation,
+            kelvin_range: match init.mode {
+                Mode::TemperatureDrift { from_kelvin, to_kelvin } => (from_kelvin, to_kelvin),
+                _ => (colors::MIN_KELVIN, colors::MIN_KELVIN),
+            },
             updater: init.mode.get_updater(),
         }
     }
 
-    pub fn update(&mut self, segment: &mut [Color]) {
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = AnimationRng::seed_from_u64(seed);
+    }
+
+    pub fn update(&mut self, segment: &mut [Color16]) {
         if let Some(f) = self.updater {
             f(self, segment);
         }
@@ -153,15 +201,19 @@ impl<'a> Background<'a> {
         self.has_been_triggered = false;
     }
 
-    fn fill_solid(&mut self, color: Color, segment: &mut [Color]) {
+    fn fill_solid(&mut self, color: Color16, segment: &mut [Color16]) {
         segment.iter_mut().for_each(|led| *led = color);
     }
 
-    fn fill_rainbow(&mut self, start_offset: u16, segment: &mut [Color]) {
+    fn fill_rainbow(&mut self, start_offset: u16, segment: &mut [Color16]) {
         let start_offset = start_offset as usize;
         let max_offset = MAX_OFFSET as usize;
         let led_count = segment.len();
         let rainbow = &self.rainbow.backer;
+        if let Palette::Gradient(gradient) = rainbow.palette() {
+            let is_forward = rainbow.is_forward();
+            return self.fill_gradient(gradient, is_forward, start_offset, segment);
+        }
         // Generate the LED Position. The LED positions are distributed evenly over
         // the entire range from 0..MAX_OFFSET, to increase the effective supersampling resolution of
         // the animation.
@@ -191,12 +243,13 @@ impl<'a> Background<'a> {
             let factor = shifted_position - bucket_start;
 
             let start_color_index = rainbow_bucket % rainbow_length;
-            let start_color = rainbow[start_color_index];
+            let start_color = rainbow[start_color_index].into();
 
             let end_color_index = (rainbow_bucket + 1) % rainbow_length;
-            let end_color = rainbow[end_color_index];
+            let end_color = rainbow[end_color_index].into();
 
-            let mid_color = colors::Color::color_lerp(
+            let mid_color = Color16::color_lerp_in(
+                self.interpolation,
                 factor as i32,
                 0,
                 distance_between_colors as i32,
@@ -207,6 +260,36 @@ impl<'a> Background<'a> {
             *led = mid_color;
         }
     }
+
+    /// Gradients already say where each of their colors goes, so rather than spacing colors
+    /// evenly like `fill_rainbow`, each LED just samples the gradient at its own position. The
+    /// gradient is squeezed into each subdivision, and read back to front when it's reversed.
+    fn fill_gradient(
+        &mut self,
+        gradient: colors::Gradient,
+        is_forward: bool,
+        start_offset: usize,
+        segment: &mut [Color16],
+    ) {
+        let max_offset = MAX_OFFSET as usize;
+        let led_count = segment.len();
+        let subdivision_length = max_offset / 1.max(self.subdivisions);
+
+        for (led_index, led) in segment.iter_mut().enumerate() {
+            let led_position = led_index * (max_offset / led_count);
+            let shifted_position = (led_position + max_offset - start_offset) % max_offset;
+
+            // scale the position within its subdivision back up to the whole gradient's range:
+            let position =
+                (shifted_position % subdivision_length) * max_offset / subdivision_length;
+            let position = match is_forward {
+                true => position,
+                false => max_offset - position,
+            };
+
+            *led = colors::gradient_color_at(gradient, position as u16, self.interpolation);
+        }
+    }
 }
 
 impl<'a> MarchingRainbow for Background<'a> {
@@ -218,8 +301,8 @@ impl<'a> MarchingRainbow for Background<'a> {
     }
 }
 
-impl<'a> MarchingRainbowMut for Background<'a> {
-    fn rainbow_mut(&mut self) -> &'a mut StatefulRainbow {
+impl<'a> MarchingRainbowMut<'a> for Background<'a> {
+    fn rainbow_mut(&mut self) -> &mut StatefulRainbow<'a> {
         &mut self.rainbow
     }
     fn frames_mut(&mut self) -> &mut Progression {
@@ -234,4 +317,7 @@ impl<'a> FadeRainbow for Background<'a> {
     fn frames(&self) -> &Progression {
         &self.frames
     }
+    fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
 }
@@ -1,4 +1,4 @@
-pub const IS_GAMMA_CORRECTION_ENABLED: bool = true;
+use crate::palette_import::{self, ImportedStop};
 
 #[allow(dead_code)]
 #[derive(Default, Copy, Clone, Debug)]
@@ -49,6 +49,727 @@ impl Color {
 
         mid_color
     }
+
+    // This works like color_lerp above, but calculates the mid-color in the color space chosen by
+    // `interpolation` instead of always going in a straight line between the RGB values.
+    pub fn color_lerp_in(
+        interpolation: Interpolation,
+        factor: i32,
+        in_min: i32,
+        in_max: i32,
+        start_color: Color,
+        end_color: Color,
+    ) -> Color {
+        match interpolation {
+            Interpolation::LinearRgb => {
+                Color::color_lerp(factor, in_min, in_max, start_color, end_color)
+            }
+            interpolation => {
+                let (start, end) = (start_color.into(), end_color.into());
+                Color16::color_lerp_in(interpolation, factor, in_min, in_max, start, end).to_color()
+            }
+        }
+    }
+}
+
+/// A color with 16 bits per channel, which is what animations work in between reading colors
+/// from their palettes and handing a frame to the `LogicalStrip`. Fades that are stacked and then
+/// dimmed keep their low bits instead of being truncated to a `Color` at every step, and the
+/// strip only rounds them to what the LEDs take once it has applied its gamma curve. A `Color`
+/// channel of 255 is 65535 here.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Color16 {
+    pub r: u16,
+    pub g: u16,
+    pub b: u16,
+}
+
+impl Color16 {
+    pub const fn new(r: u16, g: u16, b: u16) -> Self {
+        Color16 { r, g, b }
+    }
+
+    pub const fn from_color(color: Color) -> Self {
+        Color16 { r: widen(color.r), g: widen(color.g), b: widen(color.b) }
+    }
+
+    /// Rounds each channel to the nearest `Color` value.
+    pub const fn to_color(self) -> Color {
+        Color { r: narrow(self.r), g: narrow(self.g), b: narrow(self.b) }
+    }
+
+    // This works like Color::color_lerp, but keeps all 16 bits of each channel. Like that one,
+    // values outside 0..=65535 wrap around when they are cast back to a u16.
+    pub fn color_lerp(
+        factor: i32,
+        in_min: i32,
+        in_max: i32,
+        start_color: Color16,
+        end_color: Color16,
+    ) -> Color16 {
+        let (factor, in_min, in_max) = (factor as i64, in_min as i64, in_max as i64);
+        let lerp = |start: u16, end: u16| {
+            let start = start as i64;
+            let end = end as i64;
+            ((factor - in_min) * (end - start) / (in_max - in_min) + start) as u16
+        };
+
+        Color16 {
+            r: lerp(start_color.r, end_color.r),
+            g: lerp(start_color.g, end_color.g),
+            b: lerp(start_color.b, end_color.b),
+        }
+    }
+
+    // This works like Color::color_lerp_in, which hands it every fade but the straight RGB one.
+    // The other color spaces are worked out in floating point from all 16 bits of each channel,
+    // and only rounded back to 16 bits at the end.
+    pub fn color_lerp_in(
+        interpolation: Interpolation,
+        factor: i32,
+        in_min: i32,
+        in_max: i32,
+        start_color: Color16,
+        end_color: Color16,
+    ) -> Color16 {
+        let t = match in_max - in_min {
+            0 => 0.0,
+            range => (factor - in_min) as f32 / range as f32,
+        };
+        let (start, end) = (start_color.to_rgb(), end_color.to_rgb());
+        let rgb = match interpolation {
+            Interpolation::LinearRgb => {
+                return Color16::color_lerp(factor, in_min, in_max, start_color, end_color)
+            }
+            Interpolation::HsvShortest => hsv_lerp(start, end, t, false),
+            Interpolation::HsvLongest => hsv_lerp(start, end, t, true),
+            Interpolation::Oklab => {
+                let (start, end) = (Oklab::from_rgb(start), Oklab::from_rgb(end));
+                Oklab {
+                    l: lerp_f32(start.l, end.l, t),
+                    a: lerp_f32(start.a, end.a, t),
+                    b: lerp_f32(start.b, end.b, t),
+                }
+                .to_rgb()
+            }
+            Interpolation::Oklch => oklch_lerp(start, end, t),
+        };
+        Color16::from_rgb(rgb)
+    }
+
+    // each channel as a float from 0.0 to 1.0:
+    fn to_rgb(self) -> [f32; 3] {
+        [self.r, self.g, self.b].map(|channel| channel as f32 / 65535.0)
+    }
+
+    // float to int casts saturate, so out of gamut values are clipped to 0 or 65535:
+    fn from_rgb([r, g, b]: [f32; 3]) -> Self {
+        let channel = |c: f32| (c * 65535.0 + 0.5) as u16;
+        Color16 { r: channel(r), g: channel(g), b: channel(b) }
+    }
+}
+
+impl From<Color> for Color16 {
+    fn from(color: Color) -> Self {
+        Color16::from_color(color)
+    }
+}
+
+const fn widen(channel: u8) -> u16 {
+    channel as u16 * 257
+}
+
+const fn narrow(channel: u16) -> u8 {
+    ((channel as u32 * 255 + 32767) / 65535) as u8
+}
+
+/// How a layer of an animation is combined with the layers below it. Every mode except `Normal`
+/// treats the layer as light, so e.g. `Add` lets a trigger brighten a rainbow background rather
+/// than painting over it.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    /// The layer covers what's below it.
+    #[default]
+    Normal,
+
+    /// Adds the layer's light to what's below, clipping at full brightness.
+    Add,
+
+    /// Like `Add`, but eases off as the channels get brighter, so it never clips.
+    Screen,
+
+    /// Darkens what's below by the layer's brightness, white leaves it alone.
+    Multiply,
+
+    /// Takes the brighter of the layer and what's below, channel by channel.
+    Max,
+
+    /// Takes the layer's light away from what's below, stopping at off.
+    Subtract,
+}
+
+impl BlendMode {
+    /// The color a layer starts from each frame, which is the color that leaves `below`
+    /// unchanged when blended. LEDs the layer doesn't draw on stay this color, so they don't
+    /// change what shows through.
+    pub fn neutral(&self, below: Color16) -> Color16 {
+        match self {
+            BlendMode::Normal => below,
+            BlendMode::Multiply => Color16::new(u16::MAX, u16::MAX, u16::MAX),
+            _ => Color16::default(),
+        }
+    }
+
+    fn blend_channel(&self, below: u16, layer: u16) -> u16 {
+        let (below, layer, max) = (below as u32, layer as u32, u16::MAX as u32);
+        let blended = match self {
+            BlendMode::Normal => layer,
+            BlendMode::Add => (below + layer).min(max),
+            BlendMode::Screen => max - (max - below) * (max - layer) / max,
+            BlendMode::Multiply => below * layer / max,
+            BlendMode::Max => below.max(layer),
+            BlendMode::Subtract => below.saturating_sub(layer),
+        };
+        blended as u16
+    }
+}
+
+/// The blend mode and opacity of a layer. An opacity of 255 blends the layer in fully, and lower
+/// values mix the result back toward what's below, so a `Normal` layer at 128 is a 50% overlay.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LayerBlend {
+    pub mode: BlendMode,
+    pub opacity: u8,
+}
+
+impl LayerBlend {
+    /// Covers everything below it, which is how layers were drawn before blending existed.
+    pub const OPAQUE: LayerBlend = LayerBlend::new(BlendMode::Normal, u8::MAX);
+
+    pub const fn new(mode: BlendMode, opacity: u8) -> Self {
+        LayerBlend { mode, opacity }
+    }
+
+    /// Blends a layer's color onto the color below it.
+    pub fn apply(&self, below: Color16, layer: Color16) -> Color16 {
+        let opacity = self.opacity as i32;
+        let channel = |below: u16, layer: u16| {
+            let blended = self.mode.blend_channel(below, layer) as i32;
+            (below as i32 + (blended - below as i32) * opacity / 255) as u16
+        };
+        Color16 {
+            r: channel(below.r, layer.r),
+            g: channel(below.g, layer.g),
+            b: channel(below.b, layer.b),
+        }
+    }
+}
+
+impl Default for LayerBlend {
+    fn default() -> Self {
+        LayerBlend::OPAQUE
+    }
+}
+
+/// This selects the color space that fades and rainbow blends are calculated in. Going in a
+/// straight line between two RGB values is the cheapest, but a fade between two far apart hues
+/// (e.g. red to blue) passes through a dim, muddy middle. The other options keep the middle of
+/// the fade bright and saturated at the cost of some floating point math per LED.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Straight line between the raw RGB channel values.
+    #[default]
+    LinearRgb,
+
+    /// Fade through HSV, taking the shorter way around the hue wheel.
+    HsvShortest,
+
+    /// Fade through HSV, taking the longer way around the hue wheel. Red to blue will pass
+    /// through yellow, green and cyan on the way.
+    HsvLongest,
+
+    /// Straight line through the OKLab perceptual color space. Keeps the perceived lightness of
+    /// the fade even, without adding any hues that aren't in either end color.
+    Oklab,
+
+    /// Fade through the polar form of OKLab, taking the shorter way around the hue wheel. Keeps
+    /// both perceived lightness and saturation even along the fade.
+    Oklch,
+}
+
+/// A color in the OKLab perceptual color space. `l` is the perceived lightness from 0.0 to 1.0,
+/// and `a` and `b` are the green-red and blue-yellow axes.
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub struct Oklab {
+    pub l: f32,
+    pub a: f32,
+    pub b: f32,
+}
+
+impl Oklab {
+    pub fn from_color(color: Color) -> Self {
+        Oklab::from_color16(color.into())
+    }
+
+    pub fn from_color16(color: Color16) -> Self {
+        Oklab::from_rgb(color.to_rgb())
+    }
+
+    pub fn to_color(self) -> Color {
+        let channel = |c: f32| (c * 255.0 + 0.5) as u8;
+        let [r, g, b] = self.to_rgb();
+        Color { r: channel(r), g: channel(g), b: channel(b) }
+    }
+
+    pub fn to_color16(self) -> Color16 {
+        Color16::from_rgb(self.to_rgb())
+    }
+
+    // from sRGB channels between 0.0 and 1.0:
+    fn from_rgb(rgb: [f32; 3]) -> Self {
+        let [r, g, b] = rgb.map(srgb_to_linear);
+
+        let l = libm::cbrtf(0.4122215 * r + 0.5363325 * g + 0.05144599 * b);
+        let m = libm::cbrtf(0.2119035 * r + 0.6806995 * g + 0.107397 * b);
+        let s = libm::cbrtf(0.08830246 * r + 0.2817188 * g + 0.6299787 * b);
+
+        Oklab {
+            l: 0.2104543 * l + 0.7936178 * m - 0.004072047 * s,
+            a: 1.977998 * l - 2.428592 * m + 0.4505937 * s,
+            b: 0.02590404 * l + 0.7827718 * m - 0.8086758 * s,
+        }
+    }
+
+    // to sRGB channels, which are outside 0.0..=1.0 if the color is out of gamut:
+    fn to_rgb(self) -> [f32; 3] {
+        let l = self.l + 0.3963378 * self.a + 0.2158038 * self.b;
+        let m = self.l - 0.1055613 * self.a - 0.06385417 * self.b;
+        let s = self.l - 0.08948418 * self.a - 1.291486 * self.b;
+        let (l, m, s) = (l * l * l, m * m * m, s * s * s);
+
+        [
+            4.076742 * l - 3.307712 * m + 0.2309699 * s,
+            -1.268438 * l + 2.609757 * m - 0.3413194 * s,
+            -0.004196086 * l - 0.7034186 * m + 1.707615 * s,
+        ]
+        .map(linear_to_srgb)
+    }
+}
+
+// below this chroma a color is treated as grey, and takes its hue from the other end of a fade:
+const ACHROMATIC_CHROMA: f32 = 0.0001;
+
+fn oklch_lerp(start_color: [f32; 3], end_color: [f32; 3], t: f32) -> [f32; 3] {
+    let to_lch = |lab: Oklab| {
+        let chroma = libm::sqrtf(lab.a * lab.a + lab.b * lab.b);
+        (lab.l, chroma, libm::atan2f(lab.b, lab.a).to_degrees())
+    };
+    let (start_l, start_c, start_h) = to_lch(Oklab::from_rgb(start_color));
+    let (end_l, end_c, end_h) = to_lch(Oklab::from_rgb(end_color));
+
+    let start_h = if start_c < ACHROMATIC_CHROMA {
+        end_h
+    } else {
+        start_h
+    };
+    let end_h = if end_c < ACHROMATIC_CHROMA {
+        start_h
+    } else {
+        end_h
+    };
+
+    let chroma = lerp_f32(start_c, end_c, t);
+    let hue = lerp_hue(start_h, end_h, t, false).to_radians();
+    Oklab {
+        l: lerp_f32(start_l, end_l, t),
+        a: chroma * libm::cosf(hue),
+        b: chroma * libm::sinf(hue),
+    }
+    .to_rgb()
+}
+
+fn hsv_lerp(start_color: [f32; 3], end_color: [f32; 3], t: f32, is_longest: bool) -> [f32; 3] {
+    let ([start_h, start_s, start_v], [end_h, end_s, end_v]) =
+        (rgb_to_hsv(start_color), rgb_to_hsv(end_color));
+
+    // greys have no hue of their own, so use the other color's hue to avoid sweeping the wheel:
+    let start_h = if start_s == 0.0 { end_h } else { start_h };
+    let end_h = if end_s == 0.0 { start_h } else { end_h };
+
+    hsv_to_rgb([
+        lerp_hue(start_h, end_h, t, is_longest),
+        lerp_f32(start_s, end_s, t),
+        lerp_f32(start_v, end_v, t),
+    ])
+}
+
+// the hue in degrees, and the saturation and value from 0.0 to 1.0, of channels from 0.0 to 1.0.
+// Hsv does the same in whole steps, which is too coarse for a Color16:
+fn rgb_to_hsv([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+    let delta = max - min;
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let hue = if hue < 0.0 { hue + 360.0 } else { hue };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    [hue, saturation, max]
+}
+
+fn hsv_to_rgb([hue, saturation, value]: [f32; 3]) -> [f32; 3] {
+    let chroma = value * saturation;
+    let sector = hue / 60.0;
+    let x = chroma * (1.0 - libm::fabsf(sector % 2.0 - 1.0));
+    let m = value - chroma;
+    let [r, g, b] = match sector as u32 {
+        0 => [chroma, x, 0.0],
+        1 => [x, chroma, 0.0],
+        2 => [0.0, chroma, x],
+        3 => [0.0, x, chroma],
+        4 => [x, 0.0, chroma],
+        _ => [chroma, 0.0, x],
+    };
+    [r + m, g + m, b + m]
+}
+
+// interpolates between two hues in degrees, going either the short or the long way around:
+fn lerp_hue(start: f32, end: f32, t: f32, is_longest: bool) -> f32 {
+    let mut distance = (end - start) % 360.0;
+    if distance < 0.0 {
+        distance += 360.0;
+    }
+    if !is_longest && distance > 180.0 {
+        distance -= 360.0;
+    }
+    if is_longest && distance > 0.0 && distance < 180.0 {
+        distance -= 360.0;
+    }
+    let hue = (start + distance * t) % 360.0;
+    if hue < 0.0 {
+        hue + 360.0
+    } else {
+        hue
+    }
+}
+
+fn lerp_f32(start: f32, end: f32, t: f32) -> f32 {
+    start + (end - start) * t
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        libm::powf((c + 0.055) / 1.055, 2.4)
+    }
+}
+
+fn linear_to_srgb(linear: f32) -> f32 {
+    if linear <= 0.0031308 {
+        12.92 * linear
+    } else {
+        1.055 * libm::powf(linear, 1.0 / 2.4) - 0.055
+    }
+}
+
+/// A color in hue, saturation and value form. Hue is in degrees and wraps around at 360, while
+/// saturation and value use the same 0-255 range as the channels of a `Color`.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Hsv {
+    pub h: u16,
+    pub s: u8,
+    pub v: u8,
+}
+
+impl Hsv {
+    pub const fn new(h: u16, s: u8, v: u8) -> Self {
+        Hsv { h, s, v }
+    }
+
+    // returns the same color with the hue turned by `degrees`:
+    pub const fn rotate(self, degrees: u16) -> Self {
+        Hsv { h: (self.h % 360 + degrees % 360) % 360, s: self.s, v: self.v }
+    }
+
+    pub const fn to_color(self) -> Color {
+        let (h, s, v) = ((self.h % 360) as u32, self.s as u32, self.v as u32);
+        let f = h % 60;
+        // the rising and falling channels are scaled by both s / 255 and f / 60:
+        let d = 255 * 60;
+        let p = (v * (255 - s) + 127) / 255;
+        let q = (v * (d - s * f) + d / 2) / d;
+        let t = (v * (d - s * (60 - f)) + d / 2) / d;
+        let (r, g, b) = match h / 60 {
+            0 => (v, t, p),
+            1 => (q, v, p),
+            2 => (p, v, t),
+            3 => (p, q, v),
+            4 => (t, p, v),
+            _ => (v, p, q),
+        };
+        Color { r: r as u8, g: g as u8, b: b as u8 }
+    }
+
+    pub const fn from_color(color: Color) -> Self {
+        let (max, min) = channel_extents(color);
+        let delta = max - min;
+        // black has no saturation:
+        let s = match (delta * 255 + max / 2).checked_div(max) {
+            Some(s) => s,
+            None => 0,
+        };
+        Hsv { h: hue_of(color, max, delta), s: s as u8, v: max as u8 }
+    }
+}
+
+/// A color in hue, saturation and lightness form. Hue is in degrees and wraps around at 360,
+/// while saturation and lightness use the same 0-255 range as the channels of a `Color`.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Hsl {
+    pub h: u16,
+    pub s: u8,
+    pub l: u8,
+}
+
+impl Hsl {
+    pub const fn new(h: u16, s: u8, l: u8) -> Self {
+        Hsl { h, s, l }
+    }
+
+    // returns the same color with the hue turned by `degrees`:
+    pub const fn rotate(self, degrees: u16) -> Self {
+        Hsl { h: (self.h % 360 + degrees % 360) % 360, s: self.s, l: self.l }
+    }
+
+    pub const fn to_color(self) -> Color {
+        let (h, s, l) = ((self.h % 360) as u32, self.s as u32, self.l as u32);
+        let f = h % 60;
+        let chroma = (255 - (2 * l).abs_diff(255)) * s / 255;
+        let x = match (h / 60) % 2 {
+            0 => (chroma * f + 30) / 60,
+            _ => (chroma * (60 - f) + 30) / 60,
+        };
+        let m = l.saturating_sub(chroma / 2);
+        let (r, g, b) = match h / 60 {
+            0 => (chroma, x, 0),
+            1 => (x, chroma, 0),
+            2 => (0, chroma, x),
+            3 => (0, x, chroma),
+            4 => (x, 0, chroma),
+            _ => (chroma, 0, x),
+        };
+        Color { r: clamp_channel(r + m), g: clamp_channel(g + m), b: clamp_channel(b + m) }
+    }
+
+    pub const fn from_color(color: Color) -> Self {
+        let (max, min) = channel_extents(color);
+        let delta = max - min;
+        let l = (max + min).div_ceil(2);
+        let s = match delta {
+            0 => 0,
+            _ => {
+                // delta is only non-zero when max + min is strictly between 0 and 510:
+                (delta * 255 + 127) / (255 - (max + min).abs_diff(255))
+            }
+        };
+        Hsl { h: hue_of(color, max, delta), s: clamp_channel(s), l: l as u8 }
+    }
+}
+
+impl From<Hsv> for Color {
+    fn from(hsv: Hsv) -> Self {
+        hsv.to_color()
+    }
+}
+
+impl From<Color> for Hsv {
+    fn from(color: Color) -> Self {
+        Hsv::from_color(color)
+    }
+}
+
+impl From<Hsl> for Color {
+    fn from(hsl: Hsl) -> Self {
+        hsl.to_color()
+    }
+}
+
+impl From<Color> for Hsl {
+    fn from(color: Color) -> Self {
+        Hsl::from_color(color)
+    }
+}
+
+impl From<[u8; 3]> for Color {
+    fn from([r, g, b]: [u8; 3]) -> Self {
+        Color { r, g, b }
+    }
+}
+
+/// How the white channel of an RGBW LED is worked out from an RGB color.
+#[derive(Copy, Clone, Debug)]
+pub enum WhiteExtraction {
+    /// Never light the white channel, the strip shows colors exactly as an RGB strip would.
+    Off,
+
+    /// Move the part of the color shared by all three channels onto the white channel, assuming
+    /// the white LED is the same white as full red + green + blue.
+    Min,
+
+    /// Like `Min`, but for a white LED that is tinted, e.g. a warm white SK6812. The color is the
+    /// one the white LED gives on its own, and only as much white is used as can be taken out of
+    /// the color without changing its hue.
+    WhitePoint(Color),
+
+    /// Only colors that are this color, or a dimmer version of it, are sent to the white channel
+    /// and everything else is sent as RGB. This lets a palette pick out its whites explicitly,
+    /// e.g. a fade from C_WHITE to C_OFF will run on the white LED alone.
+    Explicit(Color),
+}
+
+impl Color {
+    /// Splits the color into red, green, blue and white channel values.
+    pub fn to_rgbw(&self, extraction: WhiteExtraction) -> [u8; 4] {
+        let Color { r, g, b } = *self;
+        match extraction {
+            WhiteExtraction::Off => [r, g, b, 0],
+            WhiteExtraction::Min => {
+                let w = r.min(g).min(b);
+                [r - w, g - w, b - w, w]
+            }
+            WhiteExtraction::WhitePoint(tint) => {
+                // how bright the white LED can be before it puts out more of any channel than
+                // the color has:
+                let limit = |value: u8, tint: u8| match tint {
+                    0 => 255,
+                    tint => (value as u32 * 255 / tint as u32).min(255),
+                };
+                let w = limit(r, tint.r).min(limit(g, tint.g)).min(limit(b, tint.b));
+                let remove = |value: u8, tint: u8| {
+                    value.saturating_sub(div_round(w * tint as u32, 255) as u8)
+                };
+                [
+                    remove(r, tint.r),
+                    remove(g, tint.g),
+                    remove(b, tint.b),
+                    w as u8,
+                ]
+            }
+            WhiteExtraction::Explicit(white) => {
+                let (white_max, _) = channel_extents(white);
+                let (level, _) = channel_extents(*self);
+                let white_max = white_max.max(1);
+                let matches = |value: u8, channel: u8| {
+                    value.abs_diff(div_round(channel as u32 * level, white_max) as u8) <= 1
+                };
+                match matches(r, white.r) && matches(g, white.g) && matches(b, white.b) {
+                    true => [0, 0, 0, div_round(level * 255, white_max).min(255) as u8],
+                    false => [r, g, b, 0],
+                }
+            }
+        }
+    }
+}
+
+impl Color16 {
+    /// Splits the color into red, green, blue and white channel values like `Color::to_rgbw`,
+    /// keeping all 16 bits of each.
+    pub fn to_rgbw(&self, extraction: WhiteExtraction) -> [u16; 4] {
+        let Color16 { r, g, b } = *self;
+        match extraction {
+            WhiteExtraction::Off => [r, g, b, 0],
+            WhiteExtraction::Min => {
+                let w = r.min(g).min(b);
+                [r - w, g - w, b - w, w]
+            }
+            WhiteExtraction::WhitePoint(tint) => {
+                let limit = |value: u16, tint: u8| match tint {
+                    0 => u16::MAX as u32,
+                    tint => (value as u32 * 255 / tint as u32).min(u16::MAX as u32),
+                };
+                let w = limit(r, tint.r).min(limit(g, tint.g)).min(limit(b, tint.b));
+                let remove = |value: u16, tint: u8| {
+                    value.saturating_sub(div_round(w * tint as u32, 255) as u16)
+                };
+                [
+                    remove(r, tint.r),
+                    remove(g, tint.g),
+                    remove(b, tint.b),
+                    w as u16,
+                ]
+            }
+            WhiteExtraction::Explicit(white) => {
+                let (white_max, _) = channel_extents(white);
+                let level = r.max(g).max(b) as u32;
+                let white_max = white_max.max(1);
+                // the same tolerance as a whole step of a Color channel:
+                let matches = |value: u16, channel: u8| {
+                    value.abs_diff(div_round(channel as u32 * level, white_max) as u16) <= 257
+                };
+                match matches(r, white.r) && matches(g, white.g) && matches(b, white.b) {
+                    true => [
+                        0,
+                        0,
+                        0,
+                        div_round(level * 255, white_max).min(u16::MAX as u32) as u16,
+                    ],
+                    false => [r, g, b, 0],
+                }
+            }
+        }
+    }
+}
+
+// returns the largest and smallest channel values of a color:
+const fn channel_extents(color: Color) -> (u32, u32) {
+    let (r, g, b) = (color.r as u32, color.g as u32, color.b as u32);
+    let max = if r > g { r } else { g };
+    let max = if max > b { max } else { b };
+    let min = if r < g { r } else { g };
+    let min = if min < b { min } else { b };
+    (max, min)
+}
+
+// the hue in degrees is calculated the same way for both HSV and HSL:
+const fn hue_of(color: Color, max: u32, delta: u32) -> u16 {
+    if delta == 0 {
+        return 0;
+    }
+    let (r, g, b) = (color.r as i32, color.g as i32, color.b as i32);
+    let (max, delta) = (max as i32, delta as i32);
+    let hue = if max == r {
+        round_div(60 * (g - b), delta)
+    } else if max == g {
+        120 + round_div(60 * (b - r), delta)
+    } else {
+        240 + round_div(60 * (r - g), delta)
+    };
+    hue.rem_euclid(360) as u16
+}
+
+const fn round_div(numerator: i32, denominator: i32) -> i32 {
+    match numerator >= 0 {
+        true => (numerator + denominator / 2) / denominator,
+        false => (numerator - denominator / 2) / denominator,
+    }
+}
+
+const fn div_round(numerator: u32, denominator: u32) -> u32 {
+    (numerator + denominator / 2) / denominator
+}
+
+const fn clamp_channel(value: u32) -> u8 {
+    if value > 255 {
+        255
+    } else {
+        value as u8
+    }
 }
 
 // Generic colors:
@@ -66,15 +787,156 @@ pub const C_PURPLE: Color = Color { r: 255, g: 0, b: 255 };
 pub const C_DARK_PURPLE: Color = Color { r: 255, g: 0, b: 127 };
 pub const C_WHITE: Color = Color { r: 255, g: 255, b: 255 };
 pub const C_OFF: Color = Color { r: 0, g: 0, b: 0 };
-pub const C_T_3000K: Color = Color { r: 255, g: 180, b: 107 };
-pub const C_T_3500K: Color = Color { r: 255, g: 196, b: 137 };
-pub const C_T_4000K: Color = Color { r: 255, g: 209, b: 163 };
-pub const C_T_5000K: Color = Color { r: 255, g: 228, b: 206 };
+pub const C_T_3000K: Color = kelvin_to_color(3000);
+pub const C_T_3500K: Color = kelvin_to_color(3500);
+pub const C_T_4000K: Color = kelvin_to_color(4000);
+pub const C_T_5000K: Color = kelvin_to_color(5000);
 
 // Use const generic rainbows to make iterable rainbows of various sizes. Rainbows contain a
 // list of colors in order, which will be used by animations as a color rainbow.
 pub type Rainbow<'a> = &'a [Color];
 
+/// This controls how the colors of a gradient palette change between one stop and the next.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Blend {
+    /// Hold the stop's color right up to the next stop, then switch to the next color instantly.
+    Step,
+
+    /// Blend evenly from the stop's color to the next stop's color.
+    #[default]
+    Linear,
+
+    /// Blend along a cosine curve, easing out of the stop's color and into the next one.
+    Smooth,
+}
+
+impl Blend {
+    /// Reshapes a linear `factor` between 0 and `range` according to the blend style, so it can
+    /// be handed to `Color::color_lerp_in` in place of the original factor.
+    pub fn shape(&self, factor: i32, range: i32) -> i32 {
+        match self {
+            Blend::Step => 0,
+            Blend::Linear => factor,
+            Blend::Smooth if range == 0 => factor,
+            Blend::Smooth => {
+                let t = factor as f32 / range as f32;
+                let eased = (1.0 - libm::cosf(t * core::f32::consts::PI)) / 2.0;
+                (eased * range as f32 + 0.5) as i32
+            }
+        }
+    }
+}
+
+/// A single color in a gradient palette. The color sits at `position` along the palette, where
+/// the palette covers the same 0..MAX_OFFSET range the animations use, and `blend` controls how
+/// it blends into the stop after it.
+///
+/// Positions are only used where a palette is laid out along the strip, by the
+/// `background::Mode::FillRainbow` modes. Everything that steps through a palette one color at a
+/// time, like fades, foreground marquees and trigger rainbows, gives each stop the same share
+/// and only uses its color and blend.
+#[derive(Default, Copy, Clone, Debug)]
+pub struct GradientStop {
+    pub position: u16,
+    pub color: Color,
+    pub blend: Blend,
+}
+
+/// A gradient is a list of stops in order of position. Gradients wrap around like rainbows do,
+/// so the last stop blends back into the first.
+pub type Gradient<'a> = &'a [GradientStop];
+
+/// Anything animations can take their colors from. A `Rainbow` spreads its colors evenly and
+/// blends between them linearly, while a `Gradient` places each color explicitly.
+#[derive(Copy, Clone, Debug)]
+pub enum Palette<'a> {
+    Rainbow(Rainbow<'a>),
+    Gradient(Gradient<'a>),
+}
+
+impl<'a> Palette<'a> {
+    pub fn len(&self) -> usize {
+        match self {
+            Palette::Rainbow(rainbow) => rainbow.len(),
+            Palette::Gradient(gradient) => gradient.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn color(&self, index: usize) -> &Color {
+        match self {
+            Palette::Rainbow(rainbow) => &rainbow[index],
+            Palette::Gradient(gradient) => &gradient[index].color,
+        }
+    }
+
+    /// The blend style used between the color at `index` and the one after it.
+    pub fn blend(&self, index: usize) -> Blend {
+        match self {
+            Palette::Rainbow(_) => Blend::Linear,
+            Palette::Gradient(gradient) => gradient[index].blend,
+        }
+    }
+}
+
+impl<'a> From<Rainbow<'a>> for Palette<'a> {
+    fn from(rainbow: Rainbow<'a>) -> Self {
+        Palette::Rainbow(rainbow)
+    }
+}
+
+impl<'a> From<Gradient<'a>> for Palette<'a> {
+    fn from(gradient: Gradient<'a>) -> Self {
+        Palette::Gradient(gradient)
+    }
+}
+
+/// Imported gradients blend linearly, which is what FastLED and WLED do between their stops.
+impl From<ImportedStop> for GradientStop {
+    fn from(stop: ImportedStop) -> Self {
+        GradientStop {
+            position: palette_import::index_to_position(stop.index),
+            color: stop.rgb.into(),
+            blend: Blend::Linear,
+        }
+    }
+}
+
+/// Returns the color of a gradient at `position`, where the gradient covers 0..MAX_OFFSET and
+/// wraps around from its last stop back to its first.
+pub fn gradient_color_at(
+    gradient: Gradient,
+    position: u16,
+    interpolation: Interpolation,
+) -> Color16 {
+    let max_offset = crate::animations::MAX_OFFSET as i32;
+    let position = position as i32;
+    let (first, last) = match (gradient.first(), gradient.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return C_OFF.into(),
+    };
+
+    // find the stop at or before the position, wrapping around to the last one if the position
+    // is before the first stop:
+    let (start, start_position) = match gradient.iter().rposition(|s| s.position as i32 <= position)
+    {
+        Some(index) => (&gradient[index], gradient[index].position as i32),
+        None => (last, last.position as i32 - max_offset),
+    };
+    let (end, end_position) = match gradient.iter().position(|s| s.position as i32 > position) {
+        Some(index) => (&gradient[index], gradient[index].position as i32),
+        None => (first, first.position as i32 + max_offset),
+    };
+
+    let range = end_position - start_position;
+    let factor = start.blend.shape(position - start_position, range);
+    let (start_color, end_color) = (start.color.into(), end.color.into());
+    Color16::color_lerp_in(interpolation, factor, 0, range, start_color, end_color)
+}
+
 pub const R_OFF: Rainbow = &[C_OFF];
 pub const R_ON: Rainbow = &[C_WHITE];
 pub const R_RED: Rainbow = &[C_RED];
@@ -99,10 +961,75 @@ pub const R_OB: Rainbow = &[C_ORANGE, C_OFF, C_DEEP_BLUE, C_OFF];
 pub const R_BW: Rainbow = &[C_BLUE, C_OFF, C_WHITE, C_OFF];
 pub const R_RW: Rainbow = &[C_RED, C_OFF, C_WHITE, C_OFF];
 pub const R_GW: Rainbow = &[C_GREEN, C_OFF, C_WHITE, C_OFF];
-pub const R_T_3000K: Rainbow = &[C_T_4000K];
-pub const R_T_3500K: Rainbow = &[C_T_4000K];
+pub const R_T_3000K: Rainbow = &[C_T_3000K];
+pub const R_T_3500K: Rainbow = &[C_T_3500K];
 pub const R_T_4000K: Rainbow = &[C_T_4000K];
-pub const R_T_5000K: Rainbow = &[C_T_4000K];
+pub const R_T_5000K: Rainbow = &[C_T_5000K];
+
+/// The warmest and coolest color temperatures `kelvin_to_color` will make, anything outside of
+/// this is clamped to it. This is the range the fit below was made over.
+pub const MIN_KELVIN: u32 = 1000;
+pub const MAX_KELVIN: u32 = 10_000;
+
+/// The color of a blackbody glowing at `kelvin`, scaled so its brightest channel is full on. This
+/// is Tanner Helland's fit to the blackbody curve, which is close enough to be indistinguishable
+/// on LEDs between MIN_KELVIN and MAX_KELVIN.
+pub const fn kelvin_to_color(kelvin: u32) -> Color {
+    let kelvin = if kelvin < MIN_KELVIN {
+        MIN_KELVIN
+    } else if kelvin > MAX_KELVIN {
+        MAX_KELVIN
+    } else {
+        kelvin
+    };
+    let t = kelvin as f64 / 100.0;
+
+    let r = match t <= 66.0 {
+        true => 255.0,
+        false => 329.698727446 * const_powf(t - 60.0, -0.1332047592),
+    };
+    let g = match t <= 66.0 {
+        true => 99.4708025861 * const_ln(t) - 161.1195681661,
+        false => 288.1221695283 * const_powf(t - 60.0, -0.0755148492),
+    };
+    let b = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.5177312231 * const_ln(t - 10.0) - 305.0447927307
+    };
+
+    // float to int casts saturate, so anything outside 0..=255 is clamped:
+    Color { r: (r + 0.5) as u8, g: (g + 0.5) as u8, b: (b + 0.5) as u8 }
+}
+
+/// Converts between kelvin and mireds. Equal steps in mireds look like equal steps in color
+/// temperature, which equal steps in kelvin do not.
+pub const fn kelvin_to_mired(kelvin: u32) -> u32 {
+    1_000_000 / if kelvin == 0 { 1 } else { kelvin }
+}
+
+/// The color temperature `factor` of the way from `from_kelvin` to `to_kelvin`, stepping evenly
+/// in mireds so the change looks even.
+pub const fn kelvin_lerp(from_kelvin: u32, to_kelvin: u32, factor: u32, range: u32) -> u32 {
+    let (from, to) = (kelvin_to_mired(from_kelvin) as i64, kelvin_to_mired(to_kelvin) as i64);
+    let range = if range == 0 { 1 } else { range as i64 };
+    let mired = from + (to - from) * factor as i64 / range;
+    kelvin_to_mired(mired as u32)
+}
+
+/// Makes a rainbow of `N` color temperatures stepping evenly from one to the other.
+pub const fn temperature_ramp<const N: usize>(from_kelvin: u32, to_kelvin: u32) -> [Color; N] {
+    let mut colors = [C_OFF; N];
+    let mut i = 0;
+    while i < N {
+        let range = if N > 1 { N as u32 - 1 } else { 1 };
+        colors[i] = kelvin_to_color(kelvin_lerp(from_kelvin, to_kelvin, i as u32, range));
+        i += 1;
+    }
+    colors
+}
 
 pub const fn dark_pattern(base: Color) -> [Color; 6] {
     let mut colors = [C_OFF; 6];
@@ -115,6 +1042,78 @@ pub const fn dark_pattern(base: Color) -> [Color; 6] {
     colors
 }
 
+/// Makes a rainbow of `N` colors spaced evenly around the hue wheel, starting at `start_hue`.
+pub const fn hue_wheel<const N: usize>(start_hue: u16, saturation: u8, value: u8) -> [Color; N] {
+    let mut colors = [C_OFF; N];
+    let mut i = 0;
+    while i < N {
+        let hue_shift = (i * 360 / N) as u16;
+        colors[i] = Hsv::new(start_hue, saturation, value)
+            .rotate(hue_shift)
+            .to_color();
+        i += 1;
+    }
+    colors
+}
+
+/// The base color followed by the color opposite it on the hue wheel.
+pub const fn complementary(base: Hsv) -> [Color; 2] {
+    [base.to_color(), base.rotate(180).to_color()]
+}
+
+/// The base color followed by the two colors a third of the way around the hue wheel from it.
+pub const fn triadic(base: Hsv) -> [Color; 3] {
+    [
+        base.to_color(),
+        base.rotate(120).to_color(),
+        base.rotate(240).to_color(),
+    ]
+}
+
+/// The base color with its two neighbours `spread` degrees to either side of it on the hue
+/// wheel, in hue order.
+pub const fn analogous(base: Hsv, spread: u16) -> [Color; 3] {
+    let spread = spread % 360;
+    [
+        base.rotate(360 - spread).to_color(),
+        base.to_color(),
+        base.rotate(spread).to_color(),
+    ]
+}
+
+/// Makes a rainbow of `N` colors of a single hue, stepping evenly from one saturation to another.
+pub const fn saturation_ramp<const N: usize>(hue: u16, value: u8, from: u8, to: u8) -> [Color; N] {
+    let mut colors = [C_OFF; N];
+    let mut i = 0;
+    while i < N {
+        colors[i] = Hsv::new(hue, ramp_step(from, to, i, N), value).to_color();
+        i += 1;
+    }
+    colors
+}
+
+/// Makes a rainbow of `N` colors of a single hue, stepping evenly from one value to another.
+pub const fn value_ramp<const N: usize>(hue: u16, saturation: u8, from: u8, to: u8) -> [Color; N] {
+    let mut colors = [C_OFF; N];
+    let mut i = 0;
+    while i < N {
+        colors[i] = Hsv::new(hue, saturation, ramp_step(from, to, i, N)).to_color();
+        i += 1;
+    }
+    colors
+}
+
+// the value of step i out of n evenly spaced steps from `from` to `to`, inclusive:
+const fn ramp_step(from: u8, to: u8, i: usize, n: usize) -> u8 {
+    if n <= 1 {
+        return from;
+    }
+    let (from, to) = (from as i32, to as i32);
+    (from + (to - from) * i as i32 / (n as i32 - 1)) as u8
+}
+
+pub const R_HUE_WHEEL: Rainbow = &hue_wheel::<12>(0, 255, 255);
+pub const R_DAYLIGHT_TO_CANDLE: Rainbow = &temperature_ramp::<8>(6500, 1900);
 pub const R_DARK_RED_PATTERN: Rainbow = &dark_pattern(C_RED);
 pub const R_DARK_YELLOW_PATTERN: Rainbow = &dark_pattern(C_YELLOW);
 pub const R_DARK_GREEN_PATTERN: Rainbow = &dark_pattern(C_GREEN);
@@ -126,7 +1125,15 @@ pub const R_VU_METER: Rainbow = &[
     C_GREEN, C_GREEN, C_GREEN, C_GREEN, C_GREEN, C_GREEN, C_GREEN, C_YELLOW, C_YELLOW, C_RED,
 ];
 
-pub const NUM_RAINBOWS: usize = 31;
+/// A mostly blue palette with a thin gold band, to show off gradient stops:
+pub const G_BLUE_GOLD_BAND: Gradient = &[
+    GradientStop { position: 0, color: C_BLUE, blend: Blend::Step },
+    GradientStop { position: 28_000, color: C_BLUE, blend: Blend::Smooth },
+    GradientStop { position: 32_768, color: Color { r: 255, g: 170, b: 0 }, blend: Blend::Smooth },
+    GradientStop { position: 37_500, color: C_BLUE, blend: Blend::Step },
+];
+
+pub const NUM_RAINBOWS: usize = 33;
 
 /// This is an array of the rainbow consts above that can be used to cycle through rainbows in animations.
 pub const RAINBOW_ARRAY: [&[Color]; NUM_RAINBOWS] = [
@@ -161,11 +1168,37 @@ pub const RAINBOW_ARRAY: [&[Color]; NUM_RAINBOWS] = [
     R_DARK_BLUE_PATTERN,
     R_DARK_PURPLE_PATTERN,
     R_WHITE_PATTERN,
+    R_HUE_WHEEL,
+    R_DAYLIGHT_TO_CANDLE,
 ];
 
+// The consts generated by build.rs from the files in palettes/, along with IMPORTED_PALETTES and
+// NUM_IMPORTED_PALETTES:
+include!(concat!(env!("OUT_DIR"), "/imported_palettes.rs"));
+
+pub const NUM_PALETTES: usize = NUM_RAINBOWS + NUM_IMPORTED_PALETTES;
+
+/// Every rainbow in RAINBOW_ARRAY followed by every imported palette, for cycling through all of
+/// them:
+pub const PALETTE_ARRAY: [Palette; NUM_PALETTES] = all_palettes();
+
+const fn all_palettes() -> [Palette<'static>; NUM_PALETTES] {
+    let mut palettes = [Palette::Rainbow(R_OFF); NUM_PALETTES];
+    let mut i = 0;
+    while i < NUM_RAINBOWS {
+        palettes[i] = Palette::Rainbow(RAINBOW_ARRAY[i]);
+        i += 1;
+    }
+    while i < NUM_PALETTES {
+        palettes[i] = IMPORTED_PALETTES[i - NUM_RAINBOWS];
+        i += 1;
+    }
+    palettes
+}
+
 /// A color correction table for LEDs to make them look like the color you expect:
 /// Shamelessly stolen from Adafruit's neopixel library somewhere a long time ago.
-pub static GAMMA8: [u8; 256] = [
+pub const GAMMA8: [u8; 256] = [
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1,
     1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 5, 5, 5,
     5, 6, 6, 6, 6, 7, 7, 7, 7, 8, 8, 8, 9, 9, 9, 10, 10, 10, 11, 11, 11, 12, 12, 13, 13, 13, 14,
@@ -181,3 +1214,220 @@ pub static GAMMA8: [u8; 256] = [
 
 /// This is the first color in the GAMMA8 array that is not completely turned off.
 pub const FIRST_NON_OFF_COLOR: usize = 28;
+
+/// Maps a color channel value to the value actually sent to the LED. The values are 8.8 fixed
+/// point, i.e. the LED value times 256, so that the fraction lost when rounding to a whole LED
+/// value is still there for dithering to use.
+pub type GammaTable = [u16; 256];
+
+/// Sends every value unchanged.
+pub const LINEAR: GammaTable = gamma_table(1.0);
+
+/// GAMMA8's curve, a gamma of 2.8, without the rounding to whole values.
+pub const ADAFRUIT_GAMMA: GammaTable = gamma_table(2.8);
+
+/// Maps values so that equal steps look like equal steps in brightness, using the CIE 1976 L*
+/// lightness curve. This is gentler than GAMMA8 at the dark end.
+pub const CIE_LIGHTNESS: GammaTable = cie_lightness_table();
+
+/// The gamma tables used for each channel of a strip. Different batches of LEDs respond
+/// differently, so each `PhysicalStrip` gets its own:
+#[derive(Copy, Clone)]
+pub struct GammaCurve {
+    pub r: &'static GammaTable,
+    pub g: &'static GammaTable,
+    pub b: &'static GammaTable,
+    pub w: &'static GammaTable,
+}
+
+impl GammaCurve {
+    pub const ADAFRUIT: GammaCurve = GammaCurve::uniform(&ADAFRUIT_GAMMA);
+    pub const LINEAR: GammaCurve = GammaCurve::uniform(&LINEAR);
+    pub const CIE_LIGHTNESS: GammaCurve = GammaCurve::uniform(&CIE_LIGHTNESS);
+
+    /// Uses the same table for every channel.
+    pub const fn uniform(table: &'static GammaTable) -> Self {
+        GammaCurve { r: table, g: table, b: table, w: table }
+    }
+
+    /// Runs red, green, blue and white channel values through their tables, giving 8.8 fixed
+    /// point values.
+    pub fn apply(&self, [r, g, b, w]: [u8; 4]) -> [u16; 4] {
+        [
+            self.r[r as usize],
+            self.g[g as usize],
+            self.b[b as usize],
+            self.w[w as usize],
+        ]
+    }
+
+    /// Like `apply`, but for the 16 bit channel values of a `Color16`. Values that fall between
+    /// two table entries are interpolated between them, so the low bits carry on through to the
+    /// strip's dithering.
+    pub fn apply16(&self, [r, g, b, w]: [u16; 4]) -> [u16; 4] {
+        [
+            lookup16(self.r, r),
+            lookup16(self.g, g),
+            lookup16(self.b, b),
+            lookup16(self.w, w),
+        ]
+    }
+}
+
+// looks a 16 bit value up in a gamma table. The value is first scaled to an 8.8 fixed point
+// position in the table, where 65535 lands exactly on the last entry and a widened Color channel
+// lands exactly on its own entry:
+fn lookup16(table: &GammaTable, value: u16) -> u16 {
+    let position = (value as u32 * 256 + 128) / 257;
+    let (index, fraction) = ((position >> 8) as usize, (position & 0xFF) as i32);
+    let low = table[index] as i32;
+    let high = table[(index + 1).min(255)] as i32;
+    (low + (high - low) * fraction / 256) as u16
+}
+
+/// Builds a table for a plain power-law gamma. This is a const fn so that per-channel curves can
+/// be made as consts, e.g. with `r: &gamma_table(2.4)` in a `GammaCurve` const.
+pub const fn gamma_table(gamma: f32) -> GammaTable {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let value = const_powf(i as f64 / 255.0, gamma as f64);
+        table[i] = (value * 255.0 * 256.0 + 0.5) as u16;
+        i += 1;
+    }
+    table
+}
+
+/// Builds the CIE_LIGHTNESS table. The input is treated as L* from 0 to 100 and the output is
+/// the relative luminance that gives that lightness.
+pub const fn cie_lightness_table() -> GammaTable {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let l = i as f64 * 100.0 / 255.0;
+        let luminance = match l <= 8.0 {
+            true => l / 903.3,
+            false => {
+                let f = (l + 16.0) / 116.0;
+                f * f * f
+            }
+        };
+        table[i] = (luminance * 255.0 * 256.0 + 0.5) as u16;
+        i += 1;
+    }
+    table
+}
+
+/// Scales each channel of a strip to make up for its LEDs not all being equally bright, and to
+/// tint the whole strip towards a color temperature. This works like FastLED's color correction
+/// and temperature settings, and the CORRECTION_* and TEMPERATURE_* values are the same ones.
+#[derive(Copy, Clone, Debug)]
+pub struct ColorCorrection {
+    /// How far to turn each channel down so that full red + green + blue looks white.
+    pub scale: Color,
+    /// The color white should come out as, e.g. TEMPERATURE_HALOGEN for a warmer white.
+    pub temperature: Color,
+}
+
+impl ColorCorrection {
+    pub const NONE: ColorCorrection = ColorCorrection::new(UNCORRECTED, UNCORRECTED);
+
+    pub const fn new(scale: Color, temperature: Color) -> Self {
+        ColorCorrection { scale, temperature }
+    }
+
+    /// The combined scale of the correction and the temperature for each channel, out of 255.
+    pub const fn factors(&self) -> [u8; 3] {
+        let (scale, temperature) = (self.scale, self.temperature);
+        [
+            scale8(scale.r, temperature.r),
+            scale8(scale.g, temperature.g),
+            scale8(scale.b, temperature.b),
+        ]
+    }
+
+    /// Scales the red, green and blue channel values, which are 8.8 fixed point as they come
+    /// out of a `GammaCurve`. The white channel of RGBW LEDs isn't touched, it already is
+    /// whatever white the LED makes.
+    pub fn apply(&self, [r, g, b, w]: [u16; 4]) -> [u16; 4] {
+        let [scale_r, scale_g, scale_b] = self.factors();
+        let scale = |value: u16, scale: u8| ((value as u32 * (scale as u32 + 1)) >> 8) as u16;
+        [scale(r, scale_r), scale(g, scale_g), scale(b, scale_b), w]
+    }
+}
+
+// scales value by scale / 256, but with 255 leaving the value unchanged, the way FastLED does:
+const fn scale8(value: u8, scale: u8) -> u8 {
+    ((value as u16 * (scale as u16 + 1)) >> 8) as u8
+}
+
+pub const UNCORRECTED: Color = Color { r: 255, g: 255, b: 255 };
+
+// Typical channel scales for common kinds of LEDs:
+pub const CORRECTION_TYPICAL_SMD5050: Color = Color { r: 255, g: 176, b: 240 };
+pub const CORRECTION_TYPICAL_LED_STRIP: Color = Color { r: 255, g: 176, b: 240 };
+pub const CORRECTION_TYPICAL_8MM_PIXEL: Color = Color { r: 255, g: 224, b: 140 };
+pub const CORRECTION_TYPICAL_PIXEL_STRING: Color = Color { r: 255, g: 224, b: 140 };
+
+// Color temperatures of common light sources:
+pub const TEMPERATURE_CANDLE: Color = Color { r: 255, g: 147, b: 41 };
+pub const TEMPERATURE_TUNGSTEN_40W: Color = Color { r: 255, g: 197, b: 143 };
+pub const TEMPERATURE_TUNGSTEN_100W: Color = Color { r: 255, g: 214, b: 170 };
+pub const TEMPERATURE_HALOGEN: Color = Color { r: 255, g: 241, b: 224 };
+pub const TEMPERATURE_CARBON_ARC: Color = Color { r: 255, g: 250, b: 244 };
+pub const TEMPERATURE_HIGH_NOON_SUN: Color = Color { r: 255, g: 255, b: 251 };
+pub const TEMPERATURE_DIRECT_SUNLIGHT: Color = Color { r: 255, g: 255, b: 255 };
+pub const TEMPERATURE_OVERCAST_SKY: Color = Color { r: 201, g: 226, b: 255 };
+pub const TEMPERATURE_CLEAR_BLUE_SKY: Color = Color { r: 64, g: 156, b: 255 };
+
+// x^y for x > 0, which kelvin_to_color calls with x from 6 to 40. libm can't be used in a const
+// fn, so this works it out as exp(y * ln(x)) using the series below:
+const fn const_powf(x: f64, y: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    const_exp(y * const_ln(x))
+}
+
+// ln(x) for x > 0. x is scaled into 0.5..=1 by powers of two, then the atanh series
+// ln(m) = 2 * (z + z^3/3 + z^5/5 + ...) with z = (m - 1) / (m + 1) converges quickly:
+const fn const_ln(x: f64) -> f64 {
+    const LN_2: f64 = core::f64::consts::LN_2;
+    let (mut m, mut k) = (x, 0);
+    while m < 0.5 {
+        m *= 2.0;
+        k += 1;
+    }
+    while m > 1.0 {
+        m /= 2.0;
+        k -= 1;
+    }
+    let z = (m - 1.0) / (m + 1.0);
+    let (mut term, mut sum, mut n) = (z, 0.0, 1);
+    while n < 40 {
+        sum += term / n as f64;
+        term *= z * z;
+        n += 2;
+    }
+    2.0 * sum - k as f64 * LN_2
+}
+
+// e^x, by halving x until the Taylor series converges quickly and squaring the result back up:
+const fn const_exp(x: f64) -> f64 {
+    let (mut x, mut halvings) = (x, 0);
+    while x > 0.5 || x < -0.5 {
+        x /= 2.0;
+        halvings += 1;
+    }
+    let (mut term, mut sum, mut n) = (1.0, 1.0, 1);
+    while n < 20 {
+        term *= x / n as f64;
+        sum += term;
+        n += 1;
+    }
+    while halvings > 0 {
+        sum *= sum;
+        halvings -= 1;
+    }
+    sum
+}
@@ -1,6 +1,7 @@
 use crate::animations::*;
 use crate::background;
 use crate::colors as c;
+use crate::colors::Interpolation;
 use crate::foreground;
 use crate::trigger;
 
@@ -21,60 +22,94 @@ pub const DEFAULT_NUMBER_OF_PIXELS_PER_MARQUEE_PIP: usize = 1;
 /// This background parameter struct can be used to turn off all background effects
 pub const BG_OFF: background::Parameters = background::Parameters {
     mode: background::Mode::NoBackground,
-    rainbow: c::R_OFF,
+    rainbow: c::Palette::Rainbow(c::R_OFF),
     direction: Direction::Stopped,
     is_rainbow_forward: true,
     duration_ns: 0,
     subdivisions: DEFAULT_NUMBER_OF_SUBDIVISIONS,
+    interpolation: Interpolation::LinearRgb,
+    blend: c::LayerBlend::OPAQUE,
+    mask: Mask::None,
 };
 
 /// This foreground parameter struct can be used to turn off all foreground effects
 pub const FG_OFF: foreground::Parameters = foreground::Parameters {
     mode: foreground::Mode::NoForeground,
-    rainbow: c::R_OFF,
+    rainbow: c::Palette::Rainbow(c::R_OFF),
     direction: Direction::Stopped,
     is_rainbow_forward: true,
     duration_ns: 0,
     step_time_ns: 0,
     subdivisions: DEFAULT_NUMBER_OF_SUBDIVISIONS,
     pixels_per_pixel_group: DEFAULT_NUMBER_OF_PIXELS_PER_MARQUEE_PIP,
+    interpolation: Interpolation::LinearRgb,
+    blend: c::LayerBlend::OPAQUE,
+    mask: Mask::None,
 };
 
 /// This global trigger parameter struct can be used to turn off all trigger effects.
-pub const TRIGGER_OFF: trigger::GlobalParameters =
-    trigger::GlobalParameters { rainbow: c::R_OFF, is_rainbow_forward: true, duration_ns: 0 };
+pub const TRIGGER_OFF: trigger::GlobalParameters = trigger::GlobalParameters {
+    rainbow: c::Palette::Rainbow(c::R_OFF),
+    is_rainbow_forward: true,
+    duration_ns: 0,
+    interpolation: Interpolation::LinearRgb,
+    blend: c::LayerBlend::OPAQUE,
+    mask: Mask::None,
+};
 
 /// This animation parameter struct will turn off ALL animations: fg, bg, and trigger.
 pub const ANI_ALL_OFF: AnimationParameters =
     AnimationParameters { bg: BG_OFF, fg: FG_OFF, trigger: TRIGGER_OFF };
 
+/// This background slowly drifts from daylight white to a warm evening glow and back over a day.
+pub const BG_WARM_EVENING: background::Parameters = background::Parameters {
+    mode: background::Mode::TemperatureDrift { from_kelvin: 6500, to_kelvin: 2200 },
+    rainbow: c::Palette::Rainbow(c::R_OFF),
+    direction: Direction::Positive,
+    is_rainbow_forward: true,
+    duration_ns: 24 * 60 * 60 * 1_000_000_000,
+    subdivisions: DEFAULT_NUMBER_OF_SUBDIVISIONS,
+    interpolation: Interpolation::LinearRgb,
+    blend: c::LayerBlend::OPAQUE,
+    mask: Mask::None,
+};
+
 /// This is an animation background struct used for testing
 pub const BG_TEST: background::Parameters = background::Parameters {
     mode: background::Mode::FillRainbowRotate,
-    rainbow: c::R_ROYGBIV,
+    rainbow: c::Palette::Rainbow(c::R_ROYGBIV),
     direction: Direction::Positive,
     is_rainbow_forward: true,
     duration_ns: 30_000_000_000,
     subdivisions: 0,
+    interpolation: Interpolation::LinearRgb,
+    blend: c::LayerBlend::OPAQUE,
+    mask: Mask::None,
 };
 
 /// This is an animation foreground struct used for testing
 pub const FG_TEST: foreground::Parameters = foreground::Parameters {
     mode: foreground::Mode::NoForeground,
-    rainbow: c::R_ROYGBIV,
+    rainbow: c::Palette::Rainbow(c::R_ROYGBIV),
     direction: Direction::Positive,
     is_rainbow_forward: true,
     duration_ns: 10_000_000_000,
     step_time_ns: 1_000_000_000,
     subdivisions: DEFAULT_NUMBER_OF_SUBDIVISIONS,
     pixels_per_pixel_group: 1,
+    interpolation: Interpolation::LinearRgb,
+    blend: c::LayerBlend::OPAQUE,
+    mask: Mask::None,
 };
 
 /// This is an animation trigger struct used for testing
 pub const TRIGGER_TEST: trigger::GlobalParameters = trigger::GlobalParameters {
-    rainbow: c::R_T_3000K,
+    rainbow: c::Palette::Rainbow(c::R_T_3000K),
     is_rainbow_forward: true,
     duration_ns: 10_000_000_000,
+    interpolation: Interpolation::LinearRgb,
+    blend: c::LayerBlend::OPAQUE,
+    mask: Mask::None,
 };
 
 /// This animation parameter struct will turn off ALL animations: fg, bg, and trigger.
@@ -1,7 +1,7 @@
 use crate::{
-    animations::{Direction, MAX_OFFSET},
+    animations::{Direction, Mask, MAX_OFFSET},
     colors,
-    colors::{Color, Rainbow},
+    colors::{Color16, Interpolation, LayerBlend, Palette},
     utility::{
         convert_ns_to_frames, FadeRainbow, MarchingRainbow, MarchingRainbowMut, Progression,
         StatefulRainbow,
@@ -9,7 +9,7 @@ use crate::{
 };
 use embedded_time::rate::Hertz;
 
-type FgUpdater = fn(&mut Foreground, &mut [Color]);
+type FgUpdater = fn(&mut Foreground, &mut [Color16]);
 
 /// Foreground modes are rendered second, and will animate over the background animation layer but
 /// below the trigger animations. Any trigger animations will overwrite the pixel data from the
@@ -58,38 +58,38 @@ impl Mode {
     }
 }
 
-fn marquee_solid(fg: &mut Foreground, segment: &mut [Color]) {
+fn marquee_solid(fg: &mut Foreground, segment: &mut [Color16]) {
     handle_marquee_trigger(fg);
     fg.increment_marquee_step();
-    fg.fill_marquee(fg.current_fade_color(), segment);
+    fg.fill_marquee(fg.current_fade_color().into(), segment);
 }
 
-fn marquee_solid_fixed(fg: &mut Foreground, segment: &mut [Color]) {
+fn marquee_solid_fixed(fg: &mut Foreground, segment: &mut [Color16]) {
     handle_marquee_trigger(fg);
     set_marquee_toggle(fg, segment.len());
-    fg.fill_marquee(fg.current_fade_color(), segment);
+    fg.fill_marquee(fg.current_fade_color().into(), segment);
 }
 
-fn marquee_fade(fg: &mut Foreground, segment: &mut [Color]) {
+fn marquee_fade(fg: &mut Foreground, segment: &mut [Color16]) {
     handle_marquee_trigger(fg);
     fg.increment_marquee_step();
     let color = fg.calculate_fade_color();
     fg.fill_marquee(color, segment);
 }
 
-fn marquee_fade_fixed(fg: &mut Foreground, segment: &mut [Color]) {
+fn marquee_fade_fixed(fg: &mut Foreground, segment: &mut [Color16]) {
     handle_marquee_trigger(fg);
     set_marquee_toggle(fg, segment.len());
     let color = fg.calculate_fade_color();
     fg.fill_marquee(color, segment);
 }
 
-fn vu_meter(fg: &mut Foreground, segment: &mut [Color]) {
+fn vu_meter(fg: &mut Foreground, segment: &mut [Color16]) {
     fg.current_fade_color();
     let led_count = segment.len();
     let last_on_led = fg.offset as usize / led_count;
     for led in &mut segment[last_on_led..] {
-        *led = colors::C_OFF;
+        *led = colors::C_OFF.into();
     }
 }
 
@@ -110,13 +110,16 @@ fn handle_marquee_trigger(fg: &mut Foreground) {
 /// aspects of the animation can be derived from these parameters.
 pub struct Parameters<'a> {
     pub mode: Mode,
-    pub rainbow: Rainbow<'a>,
+    pub rainbow: Palette<'a>,
     pub direction: Direction,
     pub is_rainbow_forward: bool,
     pub duration_ns: u64,
     pub step_time_ns: u64,
     pub subdivisions: usize,
     pub pixels_per_pixel_group: usize,
+    pub interpolation: Interpolation,
+    pub blend: LayerBlend,
+    pub mask: Mask<'a>,
 }
 
 #[allow(dead_code)]
@@ -130,9 +133,12 @@ pub struct Foreground<'a> {
 
     // parameters
     pub rainbow: StatefulRainbow<'a>,
+    pub blend: LayerBlend,
+    pub mask: Mask<'a>,
     direction: Direction,
     subdivisions: usize,
     pixels_per_pixel_group: usize,
+    interpolation: Interpolation,
     updater: Option<FgUpdater>,
 }
 
@@ -148,14 +154,17 @@ impl<'a> Foreground<'a> {
             marquee_position_toggle: false,
             has_been_triggered: false,
             rainbow: StatefulRainbow::new(init.rainbow, init.is_rainbow_forward),
+            blend: init.blend,
+            mask: init.mask,
             direction: init.direction,
             subdivisions: init.subdivisions,
             pixels_per_pixel_group: init.pixels_per_pixel_group,
+            interpolation: init.interpolation,
             updater: init.mode.get_updater(),
         }
     }
 
-    pub fn update(&mut self, segment: &mut [Color]) {
+    pub fn update(&mut self, segment: &mut [Color16]) {
         if let Some(f) = self.updater {
             f(self, segment);
         }
@@ -178,7 +187,7 @@ impl<'a> Foreground<'a> {
         }
     }
 
-    fn fill_marquee(&mut self, color: Color, segment: &mut [Color]) {
+    fn fill_marquee(&mut self, color: Color16, segment: &mut [Color16]) {
         for (led_index, led) in segment.iter_mut().enumerate() {
             // every time the index is evenly divisible by the number of subpixels, toggle the state
             // that the pixels should be set to:
@@ -205,8 +214,8 @@ impl<'a> MarchingRainbow for Foreground<'a> {
     }
 }
 
-impl<'a> MarchingRainbowMut for Foreground<'a> {
-    fn rainbow_mut(&mut self) -> &'a mut StatefulRainbow {
+impl<'a> MarchingRainbowMut<'a> for Foreground<'a> {
+    fn rainbow_mut(&mut self) -> &mut StatefulRainbow<'a> {
         &mut self.rainbow
     }
     fn frames_mut(&mut self) -> &mut Progression {
@@ -221,4 +230,7 @@ impl<'a> FadeRainbow for Foreground<'a> {
     fn frames(&self) -> &Progression {
         &self.frames
     }
+    fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
 }
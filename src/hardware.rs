@@ -1,4 +1,9 @@
-use bl602_hal::timer::{ConfiguredTimerChannel0, ConfiguredTimerChannel1, Preload};
+#[cfg(feature = "bl602")]
+use bl602_hal::{
+    pac,
+    timer::{ConfiguredTimerChannel0, ConfiguredTimerChannel1, Preload},
+};
+use crate::leds::ws28xx::{LogicalStrip, PhysicalStrip, Protocol};
 use core::convert::Infallible;
 use embedded_hal::digital::blocking::OutputPin;
 use embedded_time::duration::*;
@@ -43,6 +48,72 @@ where
     }
 }
 
+/// A `LedDriver` is anything that can push the encoded bytes of a `PhysicalStrip` out to the
+/// LEDs. `LogicalStrip` hands it one strip's worth of bytes at a time, already gamma corrected,
+/// in the strip's color order and with reversed strips flipped, so the driver only has to deal
+/// with getting the bytes onto the wire (or into memory, for testing).
+pub trait LedDriver {
+    /// Sends the bytes for the strip at `strip_index` in the `LogicalStrip`'s strip array.
+    fn send_strip(&mut self, strip_index: usize, strip: &PhysicalStrip, bytes: &[u8]);
+
+    /// Called once all the strips of a frame have been sent. Drivers that buffer output can use
+    /// this to flush it.
+    fn end_frame(&mut self) {}
+
+    /// Sends every strip of a frame. By default they are sent one after another, drivers that
+    /// can send them together override this.
+    fn send_frame(&mut self, logical_strip: &LogicalStrip)
+    where
+        Self: Sized,
+    {
+        logical_strip.send_all_sequential(self);
+    }
+}
+
+/// A GPIO port that can change several pins with a single write, so that every strip can be
+/// sent at once by `parallel::ParallelController`. Bit `n` of a mask is the pin of the strip at
+/// index `n`, so an implementation for a real port maps those bits onto its pin numbers.
+pub trait PortWrite {
+    /// Sets the pins in `mask` high, leaving the rest as they are.
+    fn set_high_mask(&mut self, mask: u32);
+    /// Sets the pins in `mask` low, leaving the rest as they are.
+    fn set_low_mask(&mut self, mask: u32);
+}
+
+/// Writes the pins one at a time, so the edges only line up if the writes are much quicker than
+/// a bit. It's only meant for running the parallel backend against `RecordingPin`s, on the
+/// BL602 use `GpioPort` instead.
+impl<'a, T> PortWrite for HardwareController<'a, T> {
+    // a mask only has bits for the first 32 pins, so any past those are left alone:
+    fn set_high_mask(&mut self, mask: u32) {
+        for pin in (0..self.pins.len().min(32)).filter(|pin| mask & (1 << pin) != 0) {
+            self.set_high(pin);
+        }
+    }
+
+    fn set_low_mask(&mut self, mask: u32) {
+        for pin in (0..self.pins.len().min(32)).filter(|pin| mask & (1 << pin) != 0) {
+            self.set_low(pin);
+        }
+    }
+}
+
+/// The GPIO bit-bang backend: each strip is sent on the pin with the same index as the strip.
+/// Clocked strips need a clock line as well as a data line, so they are skipped and their pins
+/// left alone; send them with `spi::SpiController` instead.
+impl<'a, T> LedDriver for HardwareController<'a, T>
+where
+    T: PeriodicTimer,
+{
+    fn send_strip(&mut self, strip_index: usize, strip: &PhysicalStrip, bytes: &[u8]) {
+        if !matches!(strip.protocol, Protocol::Ws28xx) {
+            return;
+        }
+        let bit_slice = LogicalStrip::bytes_as_bit_slice(bytes);
+        strip.send_bits(self, strip_index, bit_slice.iter().by_val());
+    }
+}
+
 pub trait PeriodicTimer {
     fn periodic_start(&mut self, time: impl Into<Nanoseconds<u64>>);
     fn periodic_wait(&mut self);
@@ -53,6 +124,7 @@ pub enum TimerError {
     WouldBlock,
 }
 
+#[cfg(feature = "bl602")]
 macro_rules! setup_periodic_timer {
     ($timer:ident) => {
         impl PeriodicTimer for $timer {
@@ -86,5 +158,74 @@ macro_rules! setup_periodic_timer {
     };
 }
 
+#[cfg(feature = "bl602")]
 setup_periodic_timer!(ConfiguredTimerChannel0);
+#[cfg(feature = "bl602")]
 setup_periodic_timer!(ConfiguredTimerChannel1);
+
+/// How many GPIO pins the BL602 has, GPIO0 to GPIO22.
+#[cfg(feature = "bl602")]
+pub const NUM_GPIO_PINS: u32 = 23;
+
+/// The BL602's GPIO port, which changes every pin of a mask with a single write to the GPIO
+/// output register. The strip at index `n` is sent on GPIO `first_pin + n`, and those pins have
+/// to be set up as outputs before the port is used.
+#[cfg(feature = "bl602")]
+pub struct GpioPort<T> {
+    first_pin: u32,
+    // the GPIO pins of the strips, so a mask can't reach past them:
+    pins: u32,
+    timer: T,
+}
+
+#[cfg(feature = "bl602")]
+impl<T> GpioPort<T> {
+    /// Returns None if the `strip_count` pins starting at `first_pin` run past GPIO22.
+    pub fn new(first_pin: u32, strip_count: u32, timer: T) -> Option<Self> {
+        if first_pin.checked_add(strip_count)? > NUM_GPIO_PINS {
+            return None;
+        }
+        let pins = ((1 << strip_count) - 1) << first_pin;
+        Some(GpioPort { first_pin, pins, timer })
+    }
+
+    // the output pins were handed out by GLB::split(), but they all share the one register, so
+    // it's written directly. The BL602 has no set or clear registers, so this reads the current
+    // outputs back and writes them all at once:
+    fn write_outputs(&mut self, outputs: impl FnOnce(u32) -> u32) {
+        let glb = unsafe { &*pac::GLB::ptr() };
+        glb.gpio_cfgctl32
+            .modify(|r, w| unsafe { w.bits(outputs(r.bits())) });
+    }
+}
+
+#[cfg(feature = "bl602")]
+impl<T> PortWrite for GpioPort<T> {
+    fn set_high_mask(&mut self, mask: u32) {
+        let mask = (mask << self.first_pin) & self.pins;
+        self.write_outputs(|outputs| outputs | mask);
+    }
+
+    fn set_low_mask(&mut self, mask: u32) {
+        let mask = (mask << self.first_pin) & self.pins;
+        self.write_outputs(|outputs| outputs & !mask);
+    }
+}
+
+#[cfg(feature = "bl602")]
+impl<T> PeriodicTimer for GpioPort<T>
+where
+    T: PeriodicTimer,
+{
+    fn periodic_start(&mut self, time: impl Into<Nanoseconds<u64>>) {
+        self.timer.periodic_start(time);
+    }
+
+    fn periodic_wait(&mut self) {
+        self.timer.periodic_wait();
+    }
+
+    fn periodic_check_timeout(&mut self) -> Result<(), TimerError> {
+        self.timer.periodic_check_timeout()
+    }
+}
@@ -1,23 +1,38 @@
 pub mod ws28xx {
     use crate::{
         colors as c,
-        hardware::{HardwareController, PeriodicTimer},
+        hardware::{HardwareController, LedDriver, PeriodicTimer},
     };
+    use arrayvec::ArrayVec;
     use bitvec::prelude::*;
     use embedded_time::duration::*;
 
+    #[derive(PartialEq, Eq)]
     pub struct StripTimings {
         pub zero_h: u32,
         pub one_h: u32,
         pub full_cycle: u32,
+        /// How long the data line has to stay low for the LEDs to latch a frame, from the
+        /// datasheet.
+        pub reset: u32,
     }
 
     #[allow(unused_variables)]
     impl StripTimings {
-        pub const WS2811_ADAFRUIT: StripTimings =
-            StripTimings { zero_h: 500_u32, one_h: 1200_u32, full_cycle: 2500_u32 };
-        pub const WS2812_ADAFRUIT: StripTimings =
-            StripTimings { zero_h: 400_u32, one_h: 800_u32, full_cycle: 1250_u32 };
+        // the current WS2811 and WS2812B datasheets both ask for a reset of at least 280us, up
+        // from the 50us of the older parts:
+        pub const WS2811_ADAFRUIT: StripTimings = StripTimings {
+            zero_h: 500_u32,
+            one_h: 1200_u32,
+            full_cycle: 2500_u32,
+            reset: 280_000_u32,
+        };
+        pub const WS2812_ADAFRUIT: StripTimings = StripTimings {
+            zero_h: 400_u32,
+            one_h: 800_u32,
+            full_cycle: 1250_u32,
+            reset: 280_000_u32,
+        };
     }
 
     pub const WS2811_DELAY_LOOPS_BEFORE_SEND: u32 = 900;
@@ -46,14 +61,207 @@ pub mod ws28xx {
         }
     }
 
+    /// The channels each LED on a strip has.
+    #[derive(Copy, Clone)]
+    pub enum PixelFormat {
+        /// Three bytes per LED, sent in the strip's `ColorOrder`.
+        Rgb,
+        /// Four bytes per LED, for RGBW strips like the SK6812 RGBW. The color bytes are sent in
+        /// the strip's `ColorOrder` followed by the white byte, so `ColorOrder::GRB` gives GRBW.
+        Rgbw(c::WhiteExtraction),
+    }
+
+    impl PixelFormat {
+        pub const fn bytes_per_led(&self) -> usize {
+            match self {
+                PixelFormat::Rgb => 3,
+                PixelFormat::Rgbw(_) => 4,
+            }
+        }
+    }
+
+    /// How a strip turns the 8.8 fixed point values coming out of its gamma curve and color
+    /// correction into the whole bytes the LEDs take. Without dithering, dim colors snap to the
+    /// nearest LED value, so everything below `colors::FIRST_NON_OFF_COLOR` is off and slow fades
+    /// step visibly. Dithering flickers between the two nearest values from frame to frame so
+    /// that on average the LED shows the level in between.
+    #[derive(Copy, Clone)]
+    pub enum Dithering {
+        /// Rounds to the nearest value.
+        Off,
+        /// Adds a threshold from an 8 frame ordered pattern before rounding down. The pattern is
+        /// offset by the LED's index so neighbouring LEDs don't flicker in step.
+        Ordered,
+        /// Carries the fraction left over after rounding down into the next frame, so nothing is
+        /// lost over time. Needs a buffer with a byte per LED byte, given to the `LogicalStrip`
+        /// with `new_with_dithering`, and falls back to `Ordered` without one.
+        ErrorCarry,
+    }
+
+    // the frame order of the ordered dither thresholds, as a bit reversed count so that each
+    // half, quarter and eighth of the pattern is spread as evenly as it can be:
+    const ORDERED_DITHER: [u16; 8] = [0, 4, 2, 6, 1, 5, 3, 7];
+
+    impl Dithering {
+        // turns an 8.8 fixed point value into the byte to send for this frame, with the fraction
+        // carried over from the frames before in carry for ErrorCarry:
+        fn quantize(&self, value: u16, frame: usize, carry: Option<u8>) -> u8 {
+            match (self, carry) {
+                (Dithering::Off, _) => ((value as u32 + 128) >> 8).min(255) as u8,
+                (Dithering::ErrorCarry, Some(carry)) => {
+                    ((value as u32 + carry as u32) >> 8).min(255) as u8
+                }
+                (Dithering::Ordered, _) | (Dithering::ErrorCarry, None) => {
+                    let threshold = ORDERED_DITHER[frame % ORDERED_DITHER.len()] * 32 + 16;
+                    ((value as u32 + threshold as u32) >> 8).min(255) as u8
+                }
+            }
+        }
+    }
+
+    /// The wire protocol a strip speaks. WS28xx strips take a single data line timed by their
+    /// `StripTimings`, the rest are clocked, two wire protocols that send their data as plain SPI
+    /// bytes with some framing around the LEDs. Clocked strips only take RGB pixels, in the order
+    /// set by their `ColorOrder`.
+    #[derive(Copy, Clone)]
+    pub enum Protocol {
+        Ws28xx,
+        /// APA102 and SK9822 strips, with `global_brightness` being the 5 bit brightness every
+        /// LED frame carries.
+        Apa102 {
+            global_brightness: u8,
+            clock_hz: u32,
+        },
+        Ws2801 {
+            clock_hz: u32,
+        },
+        Lpd8806 {
+            clock_hz: u32,
+        },
+    }
+
+    impl Protocol {
+        /// The SPI clock a clocked strip is sent at, or `None` for WS28xx strips whose timing
+        /// comes from their `StripTimings`.
+        pub fn clock_hz(&self) -> Option<u32> {
+            match *self {
+                Protocol::Ws28xx => None,
+                Protocol::Apa102 { clock_hz, .. }
+                | Protocol::Ws2801 { clock_hz }
+                | Protocol::Lpd8806 { clock_hz } => Some(clock_hz),
+            }
+        }
+
+        /// How many bytes `byte_count` bytes of LED data take on the wire, framing included.
+        pub const fn encoded_len(&self, byte_count: usize) -> usize {
+            match self {
+                Protocol::Ws28xx => byte_count,
+                Protocol::Apa102 { .. } => super::apa102::encoded_len(byte_count),
+                Protocol::Ws2801 { .. } => super::ws2801::encoded_len(byte_count),
+                Protocol::Lpd8806 { .. } => super::lpd8806::encoded_len(byte_count),
+            }
+        }
+
+        /// Frames a strip's bytes for the wire into `out`, returning how many bytes it used.
+        /// WS28xx strips have no framing, so their bytes are copied as they are.
+        pub fn encode(&self, bytes: &[u8], out: &mut [u8]) -> Result<usize, FrameError> {
+            match *self {
+                Protocol::Ws28xx => {
+                    if bytes.len() > out.len() {
+                        return Err(FrameError::TooManyBytes { capacity: out.len() });
+                    }
+                    out[..bytes.len()].copy_from_slice(bytes);
+                    Ok(bytes.len())
+                }
+                Protocol::Apa102 { global_brightness, .. } => {
+                    super::apa102::encode(bytes, global_brightness, out)
+                }
+                Protocol::Ws2801 { .. } => super::ws2801::encode(bytes, out),
+                Protocol::Lpd8806 { .. } => super::lpd8806::encode(bytes, out),
+            }
+        }
+    }
+
+    /// Everything that can go wrong when framing a strip's bytes for its protocol.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum FrameError {
+        /// There were more bytes than the output slice could hold.
+        TooManyBytes { capacity: usize },
+        /// The bytes didn't split into whole 3 byte LEDs.
+        PartialLed,
+        /// Clocked protocols only take RGB pixels.
+        UnsupportedPixelFormat,
+    }
+
+    /// How much current a strip draws, so a frame can be turned down before it is sent if it
+    /// would draw more than the strip's power supply can give.
+    #[derive(Copy, Clone)]
+    pub struct PowerModel {
+        /// The milliamps each channel of an LED draws at full duty, a byte of 255. The draw is
+        /// taken to go down in step with the byte value, which is how the LEDs' PWM works.
+        pub ma_per_channel: u32,
+        /// The milliamps each LED draws even when it's off.
+        pub idle_ma: u32,
+        /// The most current the strip may draw, in milliamps, or `None` for no limit.
+        pub max_ma: Option<u32>,
+    }
+
+    impl PowerModel {
+        /// A typical WS2812B, with no limit on its current.
+        pub const WS2812: PowerModel = PowerModel { ma_per_channel: 20, idle_ma: 1, max_ma: None };
+    }
+
     pub struct PhysicalStrip {
         pub led_count: usize,
         pub reversed: bool,
         pub color_order: ColorOrder,
+        pub pixel_format: PixelFormat,
+        /// Scales every color sent to the strip before its gamma curve, 255 being full.
+        pub brightness: u8,
+        pub gamma: c::GammaCurve,
+        pub correction: c::ColorCorrection,
+        pub dithering: Dithering,
+        pub power: PowerModel,
+        pub protocol: Protocol,
+        /// Only used by `Protocol::Ws28xx` strips.
         pub strip_timings: StripTimings,
     }
 
     impl PhysicalStrip {
+        /// A WS28xx RGB strip at full brightness with the Adafruit gamma curve, and no color
+        /// correction, dithering or current limit. Anything else can be changed from there with
+        /// struct update syntax, e.g. `PhysicalStrip { reversed: true, ..PhysicalStrip::new(..) }`,
+        /// so strips don't have to spell out every setting.
+        pub const fn new(
+            led_count: usize,
+            color_order: ColorOrder,
+            strip_timings: StripTimings,
+        ) -> Self {
+            PhysicalStrip {
+                led_count,
+                reversed: false,
+                color_order,
+                pixel_format: PixelFormat::Rgb,
+                brightness: 255,
+                gamma: c::GammaCurve::ADAFRUIT,
+                correction: c::ColorCorrection::NONE,
+                dithering: Dithering::Off,
+                power: PowerModel::WS2812,
+                protocol: Protocol::Ws28xx,
+                strip_timings,
+            }
+        }
+
+        /// Frames this strip's `bytes` for its protocol into `out`, returning how many bytes it
+        /// used. RGBW strips can only use `Protocol::Ws28xx`, since the clocked protocols frame
+        /// every 3 bytes as an LED.
+        pub fn encode(&self, bytes: &[u8], out: &mut [u8]) -> Result<usize, FrameError> {
+            if let (PixelFormat::Rgbw(_), Some(_)) = (self.pixel_format, self.protocol.clock_hz()) {
+                return Err(FrameError::UnsupportedPixelFormat);
+            }
+            self.protocol.encode(bytes, out)
+        }
+
         pub fn send_bits<T>(
             &self,
             hc: &mut HardwareController<T>,
@@ -101,96 +309,336 @@ pub mod ws28xx {
 
     pub struct LogicalStrip<'a> {
         _byte_buffer: &'a mut [u8],
-        color_buffer: &'a mut [c::Color],
-        strips: &'a [PhysicalStrip],
+        color_buffer: &'a mut [c::Color16],
+        strips: &'a mut [PhysicalStrip],
+        dither_buffer: &'a mut [u8],
+        dither_frame: usize,
+        max_ma: Option<u32>,
+        // set when limit_power turned the bytes down, so they're encoded again before the next
+        // frame is limited rather than being turned down on top of that:
+        power_limited: bool,
+        master_brightness: u16,
     }
 
     impl<'a> LogicalStrip<'a> {
         pub fn new(
             byte_buffer: &'a mut [u8],
-            color_buffer: &'a mut [c::Color],
-            strips: &'a [PhysicalStrip],
+            color_buffer: &'a mut [c::Color16],
+            strips: &'a mut [PhysicalStrip],
         ) -> Self {
-            LogicalStrip { color_buffer, strips, _byte_buffer: byte_buffer }
+            Self::new_with_dithering(byte_buffer, color_buffer, strips, &mut [])
+        }
+
+        // dither_buffer holds the fraction each byte carries into the next frame for strips using
+        // Dithering::ErrorCarry, so it should be the same length as byte_buffer:
+        pub fn new_with_dithering(
+            byte_buffer: &'a mut [u8],
+            color_buffer: &'a mut [c::Color16],
+            strips: &'a mut [PhysicalStrip],
+            dither_buffer: &'a mut [u8],
+        ) -> Self {
+            LogicalStrip {
+                color_buffer,
+                strips,
+                _byte_buffer: byte_buffer,
+                dither_buffer,
+                dither_frame: 0,
+                max_ma: None,
+                power_limited: false,
+                master_brightness: u16::MAX,
+            }
+        }
+
+        // this moves the dither pattern on a step, call it once before setting each frame's
+        // colors. ErrorCarry strips carry the fraction their bytes dropped last frame here, so a
+        // led set more than once a frame only carries it once, and leds that aren't set again
+        // keep dithering:
+        pub fn next_frame(&mut self) {
+            self.dither_frame = self.dither_frame.wrapping_add(1);
+
+            let mut start = 0;
+            for strip_index in 0..self.strips.len() {
+                let led_count = self.strips[strip_index].led_count;
+                if let Dithering::ErrorCarry = self.strips[strip_index].dithering {
+                    (start..start + led_count).for_each(|index| self.encode_led(index, true));
+                }
+                start += led_count;
+            }
         }
 
         pub fn get_color_at_index(&self, index: usize) -> c::Color {
+            self.color_buffer[index].to_color()
+        }
+
+        pub fn get_color16_at_index(&self, index: usize) -> c::Color16 {
             self.color_buffer[index]
         }
 
         // this sets the color value in the color array at index:
         pub fn set_color_at_index(&mut self, index: usize, color: c::Color) {
-            self.color_buffer[index].set_color(color);
+            self.set_color16_at_index(index, color.into());
+        }
+
+        // this sets the color value in the color array at index from a 16 bit color, which is
+        // only rounded to the strip's format after its gamma curve:
+        pub fn set_color16_at_index(&mut self, index: usize, color: c::Color16) {
+            self.color_buffer[index] = color;
+            self.encode_led(index, false);
+        }
+
+        // writes the bytes of the led at index from its color, first adding each byte's level to
+        // the error carried from the frames before if advance_carry is set:
+        fn encode_led(&mut self, index: usize, advance_carry: bool) {
+            let dithering = self.belongs_to(index).0.dithering;
+            let (led_start, levels) = self.led_levels(index, self.color_buffer[index]);
+            for (offset, level) in levels.iter().enumerate() {
+                let position = led_start + offset;
+                let frame = self.dither_frame + index;
+                let carry = self.dither_buffer.get_mut(position).map(|carry| {
+                    if advance_carry {
+                        *carry = ((*level as u32 + *carry as u32) & 0xFF) as u8;
+                    }
+                    *carry
+                });
+                self._byte_buffer[position] = dithering.quantize(*level, frame, carry);
+            }
+        }
+
+        // works out the 8.8 fixed point level of each byte of the led at index showing color,
+        // returning them along with the position of the led's first byte in the byte buffer:
+        fn led_levels(&self, index: usize, color: c::Color16) -> (usize, ArrayVec<u16, 4>) {
+            let (belongs_to, start, byte_start) = self.belongs_to(index);
 
-            let mut index = index;
-            let (belongs_to, start) = self.belongs_to(index);
+            // brightness scales the color the animations asked for, so it's applied before gamma
+            // like a dimmer in the animation would be:
+            let brightness = self.master_brightness as u32 * belongs_to.brightness as u32 / 255;
+            let dim = |v: u16| ((v as u32 * (brightness + 1)) >> 16) as u16;
+            let color = c::Color16::new(dim(color.r), dim(color.g), dim(color.b));
 
             let [r, g, b] = belongs_to.color_order.offsets();
 
-            let mut as_bytes = [0; 3];
+            let channels = match belongs_to.pixel_format {
+                PixelFormat::Rgb => belongs_to.gamma.apply16([color.r, color.g, color.b, 0]),
+                PixelFormat::Rgbw(extraction) => {
+                    belongs_to.gamma.apply16(color.to_rgbw(extraction))
+                }
+            };
+            // correction scales the brightness of each channel, so it's applied after gamma
+            // where the values are proportional to the light the LEDs put out:
+            let channels = belongs_to.correction.apply(channels);
 
-            as_bytes[r] = color.r;
-            as_bytes[g] = color.g;
-            as_bytes[b] = color.b;
+            // the white byte, if the strip has one, always goes after the color bytes:
+            let mut as_levels = [0; 4];
 
-            if c::IS_GAMMA_CORRECTION_ENABLED {
-                as_bytes[r] = c::GAMMA8[color.r as usize];
-                as_bytes[g] = c::GAMMA8[color.g as usize];
-                as_bytes[b] = c::GAMMA8[color.b as usize];
-            }
+            as_levels[r] = channels[0];
+            as_levels[g] = channels[1];
+            as_levels[b] = channels[2];
+            as_levels[3] = channels[3];
 
+            let mut index_offset = index - start;
             if belongs_to.reversed {
-                let index_offset = index - start;
-                let reversed_index_offset = belongs_to.led_count - 1 - index_offset;
-                index = start + reversed_index_offset;
+                index_offset = belongs_to.led_count - 1 - index_offset;
             }
 
-            for (i, _) in as_bytes.into_iter().enumerate() {
-                self._byte_buffer[(3 * index) + i] = as_bytes[i];
-            }
+            let bytes_per_led = belongs_to.pixel_format.bytes_per_led();
+            let led_start = byte_start + index_offset * bytes_per_led;
+            (led_start, as_levels[..bytes_per_led].iter().copied().collect())
         }
 
-        fn belongs_to(&self, index: usize) -> (&PhysicalStrip, usize) {
-            let (mut start, mut end) = (0, 0);
+        // returns the strip an index belongs to, along with the index of its first led and the
+        // position of its first byte in the byte buffer:
+        fn belongs_to(&self, index: usize) -> (&PhysicalStrip, usize, usize) {
+            let (mut start, mut end, mut byte_start) = (0, 0, 0);
 
-            for strip in self.strips {
+            for strip in self.strips.iter() {
                 end += strip.led_count;
 
                 if index < end {
-                    return (strip, start);
+                    return (strip, start, byte_start);
                 };
 
                 start = end;
+                byte_start += strip.led_count * strip.pixel_format.bytes_per_led();
             }
             panic!("Index out of bounds");
         }
 
+        // this changes the gamma curve of the strip at strip_index:
+        pub fn set_gamma(&mut self, strip_index: usize, gamma: c::GammaCurve) {
+            self.strips[strip_index].gamma = gamma;
+            self.reencode_strip(strip_index);
+        }
+
+        // this changes the brightness of the strip at strip_index:
+        pub fn set_brightness(&mut self, strip_index: usize, brightness: u8) {
+            self.strips[strip_index].brightness = brightness;
+            self.reencode_strip(strip_index);
+        }
+
+        // this sets the brightness every strip is scaled by on top of its own, as a 16 bit level
+        // so it can be ramped smoothly. Every strip is re-encoded when it changes:
+        pub fn set_master_brightness(&mut self, brightness: u16) {
+            if brightness != self.master_brightness {
+                self.master_brightness = brightness;
+                (0..self.strips.len()).for_each(|strip_index| self.reencode_strip(strip_index));
+            }
+        }
+
+        // this changes the color correction of the strip at strip_index:
+        pub fn set_correction(&mut self, strip_index: usize, correction: c::ColorCorrection) {
+            self.strips[strip_index].correction = correction;
+            self.reencode_strip(strip_index);
+        }
+
+        // re-encodes the leds of the strip at strip_index after its settings have changed, so
+        // the change shows up on the next frame:
+        fn reencode_strip(&mut self, strip_index: usize) {
+            let start: usize = self.strips[..strip_index].iter().map(|s| s.led_count).sum();
+            for index in start..start + self.strips[strip_index].led_count {
+                self.encode_led(index, false);
+            }
+        }
+
+        // this sets the most current all of the strips together may draw, in milliamps, on top of
+        // the limit each strip has in its PowerModel:
+        pub fn set_max_current(&mut self, max_ma: Option<u32>) {
+            self.max_ma = max_ma;
+        }
+
+        // this estimates the current in milliamps the strip at strip_index draws showing its
+        // encoded bytes:
+        pub fn strip_current_ma(&self, strip_index: usize) -> u32 {
+            let (idle_ma, active_ma) = self.strip_current_parts(strip_index);
+            idle_ma + active_ma
+        }
+
+        // this estimates the current in milliamps all of the strips draw showing their encoded
+        // bytes. After limit_power it stays within the limits:
+        pub fn estimated_current_ma(&self) -> u32 {
+            (0..self.strips.len())
+                .map(|strip_index| self.strip_current_ma(strip_index))
+                .sum()
+        }
+
+        // this turns the encoded bytes down evenly wherever a strip, or all of them together,
+        // would draw more than their limit. It works on the bytes in place, so call it once all
+        // of a frame's colors are set and just before sending them. The limit is always worked
+        // out from the colors, so leds that weren't set again since the last frame aren't
+        // turned down twice:
+        pub fn limit_power(&mut self) {
+            if self.power_limited {
+                (0..self.strips.len()).for_each(|strip_index| self.reencode_strip(strip_index));
+                self.power_limited = false;
+            }
+
+            for strip_index in 0..self.strips.len() {
+                if let Some(max_ma) = self.strips[strip_index].power.max_ma {
+                    let (idle_ma, active_ma) = self.strip_current_parts(strip_index);
+                    if let Some(scale) = Self::power_scale(max_ma, idle_ma, active_ma) {
+                        let range = self.strip_byte_range(strip_index);
+                        Self::scale_bytes(&mut self._byte_buffer[range], scale);
+                        self.power_limited = true;
+                    }
+                }
+            }
+
+            if let Some(max_ma) = self.max_ma {
+                let (mut idle_ma, mut active_ma) = (0, 0);
+                for strip_index in 0..self.strips.len() {
+                    let (idle, active) = self.strip_current_parts(strip_index);
+                    idle_ma += idle;
+                    active_ma += active;
+                }
+                if let Some(scale) = Self::power_scale(max_ma, idle_ma, active_ma) {
+                    Self::scale_bytes(self._byte_buffer, scale);
+                    self.power_limited = true;
+                }
+            }
+        }
+
+        // returns the current the strip at strip_index draws while off, and the current its lit
+        // channels add on top of that:
+        fn strip_current_parts(&self, strip_index: usize) -> (u32, u32) {
+            let power = self.strips[strip_index].power;
+            let duty: u32 = self
+                .strip_bytes(strip_index)
+                .iter()
+                .map(|&b| b as u32)
+                .sum();
+            let idle_ma = power.idle_ma * self.strips[strip_index].led_count as u32;
+            let active_ma = (duty as u64 * power.ma_per_channel as u64 / 255) as u32;
+            (idle_ma, active_ma)
+        }
+
+        // returns the 16.16 fixed point factor the lit channels have to be turned down by to
+        // keep within max_ma, which is 0 if the idle current alone is over it, or None if
+        // nothing is lit or they already fit:
+        fn power_scale(max_ma: u32, idle_ma: u32, active_ma: u32) -> Option<u32> {
+            match active_ma > 0 && idle_ma + active_ma > max_ma {
+                true => {
+                    let allowed_ma = max_ma.saturating_sub(idle_ma) as u64;
+                    Some((allowed_ma * 65536 / active_ma as u64) as u32)
+                }
+                false => None,
+            }
+        }
+
+        // rounds down, so the result never draws more than the scale allows:
+        fn scale_bytes(bytes: &mut [u8], scale: u32) {
+            for byte in bytes.iter_mut() {
+                *byte = ((*byte as u32 * scale) >> 16) as u8;
+            }
+        }
+
         // this fills the entire strip with a single color:
         pub fn set_strip_to_solid_color(&mut self, color: c::Color) {
             for c in &mut self.color_buffer.iter_mut() {
-                c.set_color(color);
+                *c = color.into();
             }
         }
 
         // this will iterate over all the strips and send the led data in series:
-        pub fn send_all_sequential<T>(&self, hc: &mut HardwareController<T>)
-        where
-            T: PeriodicTimer,
-        {
-            let mut start_index = 0;
+        pub fn send_all_sequential(&self, driver: &mut impl LedDriver) {
+            for (strip_index, strip) in self.strips.iter().enumerate() {
+                driver.send_strip(strip_index, strip, self.strip_bytes(strip_index));
+            }
+            driver.end_frame();
+        }
 
-            for (pin_index, strip) in self.strips.iter().enumerate() {
-                let end_index = start_index + strip.led_count;
+        // this returns the encoded bytes belonging to the strip at strip_index:
+        pub fn strip_bytes(&self, strip_index: usize) -> &[u8] {
+            &self._byte_buffer[self.strip_byte_range(strip_index)]
+        }
 
-                let start_byte_index = start_index * 3;
-                let end_byte_index = end_index * 3;
-                let bit_slice =
-                    Self::bytes_as_bit_slice(&self._byte_buffer[start_byte_index..end_byte_index]);
+        fn strip_byte_range(&self, strip_index: usize) -> core::ops::Range<usize> {
+            let byte_count = |s: &PhysicalStrip| s.led_count * s.pixel_format.bytes_per_led();
+            let start_index: usize = self.strips[..strip_index].iter().map(byte_count).sum();
+            start_index..start_index + byte_count(&self.strips[strip_index])
+        }
 
-                strip.send_bits(hc, pin_index, bit_slice.iter().by_val());
+        // this frames the bytes of the strip at strip_index for its protocol into out, returning
+        // how many bytes it used:
+        pub fn encode_strip(
+            &self,
+            strip_index: usize,
+            out: &mut [u8],
+        ) -> Result<usize, FrameError> {
+            self.strips[strip_index].encode(self.strip_bytes(strip_index), out)
+        }
 
-                start_index = end_index;
-            }
+        pub fn strips(&self) -> &[PhysicalStrip] {
+            self.strips
+        }
+
+        // this returns the color of every led in the logical strip, in logical order, before any
+        // of the strips' settings are applied:
+        pub fn colors(&self) -> &[c::Color16] {
+            self.color_buffer
+        }
+
+        // this returns the encoded bytes of every strip, in the order they are sent:
+        pub fn bytes(&self) -> &[u8] {
+            self._byte_buffer
         }
 
         // this takes an array of u8 color data and converts it into an array of bools
@@ -199,3 +647,107 @@ pub mod ws28xx {
         }
     }
 }
+
+/// APA102 and SK9822 strips. A frame starts with 32 zero bits, then each LED takes 4 bytes: three
+/// 1 bits and its 5 bit global brightness, then its three color bytes. It ends with zero bytes,
+/// 4 to latch an SK9822 and half a bit per LED more, as each LED delays the clock by half a
+/// cycle on its way down the strip.
+pub mod apa102 {
+    use super::ws28xx::FrameError;
+
+    const START_FRAME_LEN: usize = 4;
+    const LED_FLAG: u8 = 0b1110_0000;
+    const MAX_GLOBAL_BRIGHTNESS: u8 = 0b0001_1111;
+
+    const fn end_frame_len(led_count: usize) -> usize {
+        4 + led_count.div_ceil(16)
+    }
+
+    /// How many bytes `byte_count` bytes of RGB data take once framed.
+    pub const fn encoded_len(byte_count: usize) -> usize {
+        let led_count = byte_count / 3;
+        START_FRAME_LEN + led_count * 4 + end_frame_len(led_count)
+    }
+
+    /// Frames `bytes`, three to an LED, with every LED at `global_brightness`, which is cut down
+    /// to its low 5 bits.
+    pub fn encode(
+        bytes: &[u8],
+        global_brightness: u8,
+        out: &mut [u8],
+    ) -> Result<usize, FrameError> {
+        if !bytes.len().is_multiple_of(3) {
+            return Err(FrameError::PartialLed);
+        }
+        let len = encoded_len(bytes.len());
+        if len > out.len() {
+            return Err(FrameError::TooManyBytes { capacity: out.len() });
+        }
+        let brightness = LED_FLAG | (global_brightness & MAX_GLOBAL_BRIGHTNESS);
+        let (start, rest) = out[..len].split_at_mut(START_FRAME_LEN);
+        start.fill(0);
+        let (leds, end) = rest.split_at_mut(bytes.len() / 3 * 4);
+        for (led, color) in leds.chunks_exact_mut(4).zip(bytes.chunks_exact(3)) {
+            led[0] = brightness;
+            led[1..].copy_from_slice(color);
+        }
+        end.fill(0);
+        Ok(len)
+    }
+}
+
+/// WS2801 strips, which take the 24 bits of each LED without any framing. They latch once the
+/// clock has been held low for 500us, which the gap between frames gives them.
+pub mod ws2801 {
+    use super::ws28xx::FrameError;
+
+    /// How many bytes `byte_count` bytes of RGB data take on the wire, which is the same.
+    pub const fn encoded_len(byte_count: usize) -> usize {
+        byte_count
+    }
+
+    pub fn encode(bytes: &[u8], out: &mut [u8]) -> Result<usize, FrameError> {
+        if !bytes.len().is_multiple_of(3) {
+            return Err(FrameError::PartialLed);
+        }
+        if bytes.len() > out.len() {
+            return Err(FrameError::TooManyBytes { capacity: out.len() });
+        }
+        out[..bytes.len()].copy_from_slice(bytes);
+        Ok(bytes.len())
+    }
+}
+
+/// LPD8806 strips, which have 7 bits a channel. Every byte has its top bit set to mark it as
+/// color data, and a frame ends with a zero byte for each 32 LEDs to latch them.
+pub mod lpd8806 {
+    use super::ws28xx::FrameError;
+
+    const DATA_FLAG: u8 = 0b1000_0000;
+
+    const fn latch_len(led_count: usize) -> usize {
+        led_count.div_ceil(32)
+    }
+
+    /// How many bytes `byte_count` bytes of RGB data take once framed.
+    pub const fn encoded_len(byte_count: usize) -> usize {
+        byte_count + latch_len(byte_count / 3)
+    }
+
+    /// Frames `bytes`, three to an LED, dropping the lowest bit of each.
+    pub fn encode(bytes: &[u8], out: &mut [u8]) -> Result<usize, FrameError> {
+        if !bytes.len().is_multiple_of(3) {
+            return Err(FrameError::PartialLed);
+        }
+        let len = encoded_len(bytes.len());
+        if len > out.len() {
+            return Err(FrameError::TooManyBytes { capacity: out.len() });
+        }
+        let (data, latch) = out[..len].split_at_mut(bytes.len());
+        for (wire, byte) in data.iter_mut().zip(bytes) {
+            *wire = DATA_FLAG | (byte >> 1);
+        }
+        latch.fill(0);
+        Ok(len)
+    }
+}
@@ -8,13 +8,21 @@ pub mod foreground;
 pub mod hardware;
 pub mod leds;
 pub mod lighting_controller;
+pub mod palette_import;
+pub mod parallel;
+pub mod simulator;
+pub mod spi;
+pub mod transition;
 pub mod trigger;
 pub mod utility;
+pub mod waveform;
 
 use leds::ws28xx as strip;
 
+#[cfg(feature = "bl602")]
 pub static mut PROFILE: arrayvec::ArrayVec<usize, 512> = arrayvec::ArrayVec::new_const();
 
+#[cfg(feature = "bl602")]
 pub fn measure(start: usize) {
     unsafe {
         PROFILE
@@ -23,6 +31,7 @@ pub fn measure(start: usize) {
     }
 }
 
+#[cfg(feature = "bl602")]
 pub fn profile_average() -> usize {
     unsafe { PROFILE.iter().sum::<usize>() / 1.max(PROFILE.len()) }
 }
@@ -36,3 +45,13 @@ pub const fn get_total_num_leds(strips: &[strip::PhysicalStrip]) -> usize {
     }
     total
 }
+
+pub const fn get_total_num_bytes(strips: &[strip::PhysicalStrip]) -> usize {
+    let mut index = 0;
+    let mut total = 0;
+    while index < strips.len() {
+        total += strips[index].led_count * strips[index].pixel_format.bytes_per_led();
+        index += 1;
+    }
+    total
+}
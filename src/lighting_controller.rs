@@ -1,7 +1,10 @@
 use crate::animations::{Animatable, AnimationType};
-use crate::hardware::{HardwareController, PeriodicTimer};
+use crate::colors::{self, Color16};
+use crate::hardware::{LedDriver, PeriodicTimer};
 use crate::leds::ws28xx::LogicalStrip;
+use crate::transition::{ActiveTransition, Transition};
 use crate::trigger;
+use crate::utility;
 use embedded_time::duration::Nanoseconds;
 use embedded_time::fixed_point::FixedPoint;
 use embedded_time::rate::Hertz;
@@ -9,10 +12,42 @@ use embedded_time::rate::Hertz;
 pub struct LightingController<'a, Timer, const N_ANI: usize> {
     logical_strip: LogicalStrip<'a>,
     animations: [&'a mut dyn Animatable<'a>; N_ANI],
+    transitions: [Option<ActiveTransition<'a>>; N_ANI],
+    brightness: BrightnessFade,
     frame_rate: Hertz,
     timer: &'a mut Timer,
 }
 
+// the master brightness, as a 16 bit level moving a step each frame towards its target:
+#[derive(Copy, Clone)]
+struct BrightnessFade {
+    level: u16,
+    target: u16,
+    step: u16,
+}
+
+impl BrightnessFade {
+    const FULL: BrightnessFade = BrightnessFade { level: u16::MAX, target: u16::MAX, step: 0 };
+
+    fn fade_to(&mut self, target: u16, frames: usize) {
+        self.target = target;
+        match frames {
+            0 => self.level = target,
+            _ => {
+                let distance = self.level.abs_diff(target) as usize;
+                self.step = distance.div_ceil(frames).max(1) as u16;
+            }
+        }
+    }
+
+    fn next_frame(&mut self) {
+        self.level = match self.level < self.target {
+            true => self.level.saturating_add(self.step).min(self.target),
+            false => self.level.saturating_sub(self.step).max(self.target),
+        };
+    }
+}
+
 impl<'a, Timer, const N_ANI: usize> LightingController<'a, Timer, N_ANI>
 where
     Timer: PeriodicTimer,
@@ -24,7 +59,16 @@ where
         timer: &'a mut Timer,
     ) -> Self {
         let frame_rate = frame_rate.into();
-        let lc = LightingController { logical_strip, animations, frame_rate, timer };
+        let transitions = [(); N_ANI].map(|_| None);
+        let brightness = BrightnessFade::FULL;
+        let lc = LightingController {
+            logical_strip,
+            animations,
+            transitions,
+            brightness,
+            frame_rate,
+            timer,
+        };
         // calculate the period of the frame rate in nanoseconds
         let frame_period = 1_000_000_000_u64 / frame_rate.integer() as u64; // 1E9 Nanoseconds / Hz = Period in ns
 
@@ -33,21 +77,38 @@ where
         lc
     }
 
-    pub fn update(&mut self, hc: &mut HardwareController<impl PeriodicTimer>) {
+    pub fn update(&mut self, driver: &mut impl LedDriver) {
         // Only update if it's been longer than the frame rate period since the last update:
         if self.timer.periodic_check_timeout().is_ok() {
-            for animation in self.animations.iter_mut() {
+            self.logical_strip.next_frame();
+            self.brightness.next_frame();
+            self.logical_strip
+                .set_master_brightness(self.brightness.level);
+            let animations = self.animations.iter_mut().zip(self.transitions.iter_mut());
+            for (animation, transition) in animations {
                 animation.update();
 
-                let segment = animation.segment();
-                let translater = animation.translation_array();
-                let translated = translater.iter().zip(segment.iter());
+                match transition {
+                    None => {
+                        let segment = animation.segment();
+                        let translater = animation.translation_array();
+                        let translated = translater.iter().zip(segment.iter());
 
-                for (&index, &color) in translated {
-                    self.logical_strip.set_color_at_index(index, color);
+                        for (&index, &color) in translated {
+                            self.logical_strip.set_color16_at_index(index, color);
+                        }
+                    }
+                    Some(active) => {
+                        active.outgoing.update();
+                        Self::blend_transition(&mut self.logical_strip, active, *animation);
+                        if active.advance() {
+                            *transition = None;
+                        }
+                    }
                 }
             }
-            self.logical_strip.send_all_sequential(hc);
+            self.logical_strip.limit_power();
+            driver.send_frame(&self.logical_strip);
         }
     }
 
@@ -55,11 +116,131 @@ where
         self.animations[animation_index].trigger(params, self.frame_rate);
     }
 
+    /// Sends a trigger to one layer of an animation's stack, rather than to every layer the
+    /// trigger's mode is meant for.
+    pub fn trigger_layer(
+        &mut self,
+        animation_index: usize,
+        layer_index: usize,
+        params: &trigger::Parameters,
+    ) {
+        self.animations[animation_index].trigger_layer(layer_index, params, self.frame_rate);
+    }
+
     pub fn set_offset(&mut self, animation_index: usize, a_type: AnimationType, offset: u16) {
         self.animations[animation_index].set_offset(a_type, offset);
     }
 
+    pub fn set_layer_offset(&mut self, animation_index: usize, layer_index: usize, offset: u16) {
+        self.animations[animation_index].set_layer_offset(layer_index, offset);
+    }
+
+    /// Reseeds the random effects of every animation. Each animation gets a different seed
+    /// derived from `seed`, so they don't all make the same choices.
+    pub fn set_seed(&mut self, seed: u64) {
+        for (index, animation) in self.animations.iter_mut().enumerate() {
+            animation.set_seed(seed.wrapping_add(index as u64));
+        }
+    }
+
+    /// Sets the brightness of every strip at once, 255 being full. The animations are dimmed
+    /// without changing their parameters, and each strip's own brightness applies on top.
+    pub fn set_brightness(&mut self, level: u8) {
+        self.fade_to(level, 0);
+    }
+
+    /// Ramps the brightness of every strip from where it is now to `level` over `duration_ns`,
+    /// a step each frame. A new fade starts from wherever the last one had got to.
+    pub fn fade_to(&mut self, level: u8, duration_ns: u64) {
+        let frames = utility::convert_ns_to_frames(duration_ns, self.frame_rate);
+        self.brightness.fade_to(level as u16 * 257, frames);
+    }
+
+    /// Sets the brightness of one strip, on top of the brightness of every strip.
+    pub fn set_strip_brightness(&mut self, strip_index: usize, level: u8) {
+        self.logical_strip.set_brightness(strip_index, level);
+    }
+
+    /// Returns the brightness the current frame was rendered at, which is part of the way to the
+    /// target while fading.
+    pub fn brightness(&self) -> u8 {
+        ((self.brightness.level as u32 + 128) / 257) as u8
+    }
+
+    pub fn frame_rate(&self) -> Hertz {
+        self.frame_rate
+    }
+
+    pub fn logical_strip(&self) -> &LogicalStrip<'a> {
+        &self.logical_strip
+    }
+
     pub fn replace_animation(&mut self, index: usize, new_anim: &'a mut dyn Animatable<'a>) {
-        self.animations[index] = new_anim;
+        self.replace_animation_with(index, new_anim, Transition::Cut, 0);
+    }
+
+    /// Replaces an animation, moving over to the new one with `transition` over `duration_ns`.
+    /// Both animations keep running until the transition has finished. Replacing an animation
+    /// that is already in a transition drops the animation it was moving away from, and
+    /// animations longer than MAX_NUM_TRANSITION_LEDS are cut over.
+    pub fn replace_animation_with(
+        &mut self,
+        index: usize,
+        new_anim: &'a mut dyn Animatable<'a>,
+        transition: Transition,
+        duration_ns: u64,
+    ) {
+        let outgoing = core::mem::replace(&mut self.animations[index], new_anim);
+        let frames = utility::convert_ns_to_frames(duration_ns, self.frame_rate);
+        self.transitions[index] = match (transition, frames) {
+            (Transition::Cut, _) | (_, 0..=1) => None,
+            _ => {
+                let seed = utility::default_seed().wrapping_add(index as u64);
+                let incoming = &*self.animations[index];
+                ActiveTransition::new(outgoing, incoming, transition, frames, seed)
+            }
+        };
+    }
+
+    /// Returns true while the animation at `index` is still moving over from the one it replaced.
+    pub fn is_transitioning(&self, index: usize) -> bool {
+        self.transitions[index].is_some()
+    }
+
+    fn blend_transition(
+        logical_strip: &mut LogicalStrip<'a>,
+        active: &ActiveTransition<'a>,
+        incoming: &dyn Animatable<'a>,
+    ) {
+        let (outgoing_leds, outgoing_segment) =
+            (active.outgoing.translation_array(), active.outgoing.segment());
+        let (incoming_leds, incoming_segment) = (incoming.translation_array(), incoming.segment());
+        let off: Color16 = colors::C_OFF.into();
+
+        // LEDs are matched by their physical index, which the transition has looked up. The ones
+        // only the outgoing animation covers fade out to off:
+        let outgoing = outgoing_leds
+            .iter()
+            .zip(outgoing_segment.iter())
+            .enumerate();
+        for (position, (&index, &color)) in outgoing {
+            if !active.is_covered(position) {
+                let color = active.mix(position, outgoing_leds.len(), color, off);
+                logical_strip.set_color16_at_index(index, color);
+            }
+        }
+
+        let incoming = incoming_leds
+            .iter()
+            .zip(incoming_segment.iter())
+            .enumerate();
+        for (position, (&index, &color)) in incoming {
+            let from = match active.outgoing_position(position) {
+                Some(outgoing_position) => outgoing_segment[outgoing_position],
+                None => off,
+            };
+            let color = active.mix(position, incoming_leds.len(), from, color);
+            logical_strip.set_color16_at_index(index, color);
+        }
     }
 }
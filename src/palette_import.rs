@@ -0,0 +1,286 @@
+//! Parsers for palettes made in other tools: FastLED `DEFINE_GRADIENT_PALETTE` arrays, WLED
+//! palette JSON, and GIMP `.gpl` files. This module only uses `core`, so as well as being used
+//! at runtime it is pulled into `build.rs` with `#[path]` to turn everything in `palettes/` into
+//! consts in `colors`.
+//!
+//! None of the parsers allocate. They fill a caller-provided slice of anything that can be made
+//! from an `ImportedStop` (or an `[u8; 3]` for `.gpl` files) and return how many entries they
+//! wrote, so `colors::GradientStop` and `colors::Color` buffers can be filled directly.
+
+use core::fmt::{self, Write};
+
+/// A stop in an imported gradient. `index` is on FastLED's 0..=255 scale, use
+/// `index_to_position` to map it onto the 0..MAX_OFFSET scale the animations use.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ImportedStop {
+    pub index: u8,
+    pub rgb: [u8; 3],
+}
+
+/// Everything that can go wrong when importing a palette.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImportError {
+    /// The palette had no colors in it.
+    Empty,
+    /// There were more entries than the output slice could hold.
+    TooManyEntries { capacity: usize },
+    /// A gradient's data ended part way through a stop.
+    TruncatedStop,
+    /// A stop's index was lower than the one before it.
+    IndexOutOfOrder { entry: usize },
+    /// The file didn't start the way the format requires, e.g. a `.gpl` file without the
+    /// `GIMP Palette` header or WLED JSON without a `"palette"` array.
+    MissingHeader,
+    /// A value couldn't be read. For `.gpl` files this is the line number, otherwise it is the
+    /// number of the value in the palette data, both starting from 0.
+    BadValue { entry: usize },
+}
+
+/// Maps a FastLED gradient index onto the 0..MAX_OFFSET range, so 255 lands on MAX_OFFSET.
+pub const fn index_to_position(index: u8) -> u16 {
+    index as u16 * 257
+}
+
+/// Reads a FastLED gradient from its raw bytes, as in the array `DEFINE_GRADIENT_PALETTE`
+/// produces: repeating groups of index, red, green, blue.
+pub fn parse_fastled<T: From<ImportedStop>>(
+    bytes: &[u8],
+    out: &mut [T],
+) -> Result<usize, ImportError> {
+    collect_fastled(bytes.iter().map(|b| Ok(*b)), out)
+}
+
+/// Reads the values between the braces of a `DEFINE_GRADIENT_PALETTE` in C source, which may be
+/// written in decimal or hex and may contain `//` comments.
+pub fn parse_fastled_source<T: From<ImportedStop>>(
+    body: &str,
+    out: &mut [T],
+) -> Result<usize, ImportError> {
+    let values = c_values(body).enumerate().map(|(entry, value)| {
+        let value = match value
+            .strip_prefix("0x")
+            .or_else(|| value.strip_prefix("0X"))
+        {
+            Some(hex) => u8::from_str_radix(hex, 16),
+            None => value.parse(),
+        };
+        value.map_err(|_| ImportError::BadValue { entry })
+    });
+    collect_fastled(values, out)
+}
+
+fn collect_fastled<T: From<ImportedStop>>(
+    mut values: impl Iterator<Item = Result<u8, ImportError>>,
+    out: &mut [T],
+) -> Result<usize, ImportError> {
+    let mut stops = StopWriter::new(out);
+    while let Some(index) = values.next() {
+        let mut rgb = [0; 3];
+        for channel in rgb.iter_mut() {
+            *channel = values.next().ok_or(ImportError::TruncatedStop)??;
+        }
+        stops.push(ImportedStop { index: index?, rgb })?;
+    }
+    stops.finish()
+}
+
+/// Splits the body of a C array into its values, skipping whitespace and `//` comments.
+fn c_values(body: &str) -> impl Iterator<Item = &str> {
+    body.lines()
+        .map(|line| line.split("//").next().unwrap_or(""))
+        .flat_map(|line| line.split(','))
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+/// Walks through C source yielding the name and the text between the braces of each
+/// `DEFINE_GRADIENT_PALETTE` in it, ready for `parse_fastled_source`.
+pub struct FastLedPalettes<'t> {
+    rest: &'t str,
+}
+
+impl<'t> FastLedPalettes<'t> {
+    pub fn new(source: &'t str) -> Self {
+        Self { rest: source }
+    }
+}
+
+impl<'t> Iterator for FastLedPalettes<'t> {
+    type Item = (&'t str, &'t str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        const MACRO: &str = "DEFINE_GRADIENT_PALETTE";
+        let start = self.rest.find(MACRO)? + MACRO.len();
+        let rest = &self.rest[start..];
+        let (name, rest) = rest.split_once('(')?.1.split_once(')')?;
+        let (body, rest) = rest.split_once('{')?.1.split_once('}')?;
+        self.rest = rest;
+        Some((name.trim(), body))
+    }
+}
+
+/// Reads a WLED custom palette, e.g. `{"palette":[0,"ff0000",255,"0000ff"]}`. WLED also accepts
+/// each color written out as three numbers, `[0,255,0,0,255,0,0,255]`, and so does this.
+pub fn parse_wled<T: From<ImportedStop>>(json: &str, out: &mut [T]) -> Result<usize, ImportError> {
+    let (_, rest) = json
+        .split_once("\"palette\"")
+        .ok_or(ImportError::MissingHeader)?;
+    let rest = rest
+        .trim_start()
+        .strip_prefix(':')
+        .ok_or(ImportError::MissingHeader)?;
+    let rest = rest
+        .trim_start()
+        .strip_prefix('[')
+        .ok_or(ImportError::MissingHeader)?;
+    let (array, _) = rest.split_once(']').ok_or(ImportError::MissingHeader)?;
+
+    let values = array.split(',').map(str::trim).enumerate();
+    let mut values = values.filter(|(_, value)| !value.is_empty()).peekable();
+    let number = |(entry, value): (usize, &str)| {
+        value
+            .parse::<u8>()
+            .map_err(|_| ImportError::BadValue { entry })
+    };
+
+    let mut stops = StopWriter::new(out);
+    while let Some(index) = values.next() {
+        let index = number(index)?;
+        let rgb = match values.next_if(|(_, value)| value.starts_with('"')) {
+            Some((entry, value)) => {
+                parse_hex_color(value.trim_matches('"')).ok_or(ImportError::BadValue { entry })?
+            }
+            None => {
+                let mut rgb = [0; 3];
+                for channel in rgb.iter_mut() {
+                    *channel = number(values.next().ok_or(ImportError::TruncatedStop)?)?;
+                }
+                rgb
+            }
+        };
+        stops.push(ImportedStop { index, rgb })?;
+    }
+    stops.finish()
+}
+
+fn is_gpl_header(line: &str) -> bool {
+    line.starts_with("Name:") || line.starts_with("Columns:")
+}
+
+fn parse_hex_color(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Reads the colors from a GIMP `.gpl` palette. These have no positions, so they come out as
+/// plain colors to be used as a `Rainbow`.
+pub fn parse_gpl<T: From<[u8; 3]>>(text: &str, out: &mut [T]) -> Result<usize, ImportError> {
+    let mut lines = text.lines().enumerate();
+    match lines.next() {
+        Some((_, header)) if header.trim() == "GIMP Palette" => {}
+        _ => return Err(ImportError::MissingHeader),
+    }
+
+    let mut count = 0;
+    for (entry, line) in lines {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || is_gpl_header(line) {
+            // blank lines, comments and the Name: / Columns: headers
+            continue;
+        }
+        let mut rgb = [0; 3];
+        let mut channels = line.split_whitespace();
+        for channel in rgb.iter_mut() {
+            let value = channels.next().and_then(|c| c.parse().ok());
+            *channel = value.ok_or(ImportError::BadValue { entry })?;
+        }
+        let capacity = out.len();
+        let slot = out
+            .get_mut(count)
+            .ok_or(ImportError::TooManyEntries { capacity })?;
+        *slot = T::from(rgb);
+        count += 1;
+    }
+    finish(count)
+}
+
+/// Fills an output slice with stops, checking they're in order as it goes.
+struct StopWriter<'o, T> {
+    out: &'o mut [T],
+    count: usize,
+    last_index: u8,
+}
+
+impl<'o, T: From<ImportedStop>> StopWriter<'o, T> {
+    fn new(out: &'o mut [T]) -> Self {
+        Self { out, count: 0, last_index: 0 }
+    }
+
+    fn push(&mut self, stop: ImportedStop) -> Result<(), ImportError> {
+        if stop.index < self.last_index {
+            return Err(ImportError::IndexOutOfOrder { entry: self.count });
+        }
+        let capacity = self.out.len();
+        let slot = self
+            .out
+            .get_mut(self.count)
+            .ok_or(ImportError::TooManyEntries { capacity })?;
+        *slot = T::from(stop);
+        self.count += 1;
+        self.last_index = stop.index;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<usize, ImportError> {
+        finish(self.count)
+    }
+}
+
+fn finish(count: usize) -> Result<usize, ImportError> {
+    match count {
+        0 => Err(ImportError::Empty),
+        count => Ok(count),
+    }
+}
+
+/// Writes an imported gradient out as Rust source for a `colors::Gradient` const. This is what
+/// `build.rs` uses to turn the files in `palettes/` into consts.
+pub fn write_gradient(out: &mut impl Write, name: &str, stops: &[ImportedStop]) -> fmt::Result {
+    writeln!(out, "pub const {}: Gradient = &[", name)?;
+    for stop in stops {
+        let [r, g, b] = stop.rgb;
+        let position = index_to_position(stop.index);
+        writeln!(
+            out,
+            "    GradientStop {{ position: {}, color: Color {{ r: {}, g: {}, b: {} }}, blend: Blend::Linear }},",
+            position, r, g, b
+        )?;
+    }
+    writeln!(out, "];")
+}
+
+/// Writes imported colors out as Rust source for a `colors::Rainbow` const.
+pub fn write_rainbow(out: &mut impl Write, name: &str, colors: &[[u8; 3]]) -> fmt::Result {
+    writeln!(out, "pub const {}: Rainbow = &[", name)?;
+    for [r, g, b] in colors {
+        writeln!(out, "    Color {{ r: {}, g: {}, b: {} }},", r, g, b)?;
+    }
+    writeln!(out, "];")
+}
+
+/// Turns a palette or file name into a const name in the style of the rest of `colors`, e.g.
+/// `("G_", "Sunset Real")` becomes `G_SUNSET_REAL`.
+pub fn write_const_name(out: &mut impl Write, prefix: &str, name: &str) -> fmt::Result {
+    out.write_str(prefix)?;
+    for c in name.trim_end_matches("_gp").chars() {
+        match c.is_ascii_alphanumeric() {
+            true => out.write_char(c.to_ascii_uppercase())?,
+            false => out.write_char('_')?,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,143 @@
+//! Sends every WS28xx strip at once instead of one after another. The strips' bytes are
+//! transposed into bit-planes, one for each bit time, holding that bit of every strip in a single
+//! word with a bit per pin, so that each edge of the waveform is one port-wide write. A frame
+//! then takes as long as the longest strip rather than all of them added up.
+//!
+//! Strips that run out of bytes before the longest one are left out of the planes from there
+//! on, so their pins stay low and their LEDs see the end of the frame instead of extra bits.
+
+use crate::hardware::{LedDriver, PeriodicTimer, PortWrite};
+use crate::leds::ws28xx::{
+    LogicalStrip, PhysicalStrip, Protocol, StripTimings, WS2811_DELAY_LOOPS_BEFORE_SEND,
+};
+use arrayvec::ArrayVec;
+use embedded_time::duration::*;
+
+/// How many strips can be sent in parallel, one for each bit of a port write.
+pub const MAX_PARALLEL_STRIPS: usize = 32;
+
+/// One bit time of every strip, with bit `n` of each word belonging to the strip at index `n`.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BitPlane {
+    /// The strips that still have data, whose pins go high at the start of the bit.
+    pub active: u32,
+    /// The strips sending a one, whose pins stay high for the longer part of the bit.
+    pub ones: u32,
+}
+
+/// Transposes the byte at `byte_index` of each strip into the 8 planes for its bits, most
+/// significant first.
+pub fn transpose(strips: &[&[u8]], byte_index: usize) -> [BitPlane; 8] {
+    assert!(strips.len() <= MAX_PARALLEL_STRIPS, "Too many strips to send in parallel");
+    let mut planes = [BitPlane::default(); 8];
+    for (strip_index, bytes) in strips.iter().enumerate() {
+        if let Some(&byte) = bytes.get(byte_index) {
+            let pin = 1 << strip_index;
+            for (bit, plane) in planes.iter_mut().enumerate() {
+                plane.active |= pin;
+                if byte & (0x80 >> bit) != 0 {
+                    plane.ones |= pin;
+                }
+            }
+        }
+    }
+    planes
+}
+
+/// Every plane of a frame, in the order they are sent. There are 8 for each byte of the longest
+/// strip, and they are worked out a byte at a time so the frame never has to be held transposed.
+pub fn bit_planes<'b>(strips: &'b [&'b [u8]]) -> impl Iterator<Item = BitPlane> + 'b {
+    let longest = strips.iter().map(|bytes| bytes.len()).max().unwrap_or(0);
+    (0..longest).flat_map(move |byte_index| transpose(strips, byte_index))
+}
+
+/// The parallel backend: the strip at index `n` is sent on the pin at bit `n` of the port, which
+/// on the BL602 is a `hardware::GpioPort`. Clocked strips are skipped, since they can't be
+/// bit-banged, and the WS28xx strips all have to share their timings.
+pub struct ParallelController<P> {
+    port: P,
+}
+
+impl<P> ParallelController<P>
+where
+    P: PortWrite + PeriodicTimer,
+{
+    pub fn new(port: P) -> Self {
+        Self { port }
+    }
+
+    pub fn port(&mut self) -> &mut P {
+        &mut self.port
+    }
+
+    // sends the planes with the same split as send_bits, high for the first third of each bit,
+    // the zeroes dropping after it and the ones after the second third:
+    fn send_planes(
+        &mut self,
+        timings: &StripTimings,
+        pins: u32,
+        planes: impl Iterator<Item = BitPlane>,
+    ) {
+        self.port
+            .periodic_start((timings.full_cycle / 3).nanoseconds());
+        // keep the data pins low long enough for the leds to reset
+        self.port.set_low_mask(pins);
+        for _ in 0..WS2811_DELAY_LOOPS_BEFORE_SEND {
+            self.port.periodic_wait();
+        }
+        for plane in planes {
+            self.port.set_high_mask(plane.active);
+            self.port.periodic_wait();
+            self.port.set_low_mask(plane.active & !plane.ones);
+            self.port.periodic_wait();
+            self.port.set_low_mask(plane.ones);
+            self.port.periodic_wait();
+        }
+    }
+}
+
+impl<P> LedDriver for ParallelController<P>
+where
+    P: PortWrite + PeriodicTimer,
+{
+    fn send_strip(&mut self, strip_index: usize, strip: &PhysicalStrip, bytes: &[u8]) {
+        // past MAX_PARALLEL_STRIPS there's no pin to send the strip on:
+        if !matches!(strip.protocol, Protocol::Ws28xx) || strip_index >= MAX_PARALLEL_STRIPS {
+            return;
+        }
+        let mut strips: ArrayVec<&[u8], MAX_PARALLEL_STRIPS> = ArrayVec::new();
+        (0..strip_index).for_each(|_| strips.push(&[]));
+        strips.push(bytes);
+        self.send_planes(&strip.strip_timings, 1 << strip_index, bit_planes(&strips));
+    }
+
+    fn send_frame(&mut self, logical_strip: &LogicalStrip) {
+        assert!(
+            logical_strip.strips().len() <= MAX_PARALLEL_STRIPS,
+            "Too many strips to send in parallel"
+        );
+        let is_ws28xx = |strip: &&PhysicalStrip| matches!(strip.protocol, Protocol::Ws28xx);
+        let first = match logical_strip.strips().iter().find(is_ws28xx) {
+            Some(first) => first,
+            None => return,
+        };
+        let mut strips: ArrayVec<&[u8], MAX_PARALLEL_STRIPS> = ArrayVec::new();
+        let mut pins = 0;
+        for (strip_index, strip) in logical_strip.strips().iter().enumerate() {
+            match strip.protocol {
+                Protocol::Ws28xx => {
+                    assert!(
+                        strip.strip_timings == first.strip_timings,
+                        "Parallel strips need the same timings"
+                    );
+                    strips.push(logical_strip.strip_bytes(strip_index));
+                    pins |= 1 << strip_index;
+                }
+                // clocked strips are left out of the planes, so their pins aren't touched:
+                _ => strips.push(&[]),
+            }
+        }
+        self.send_planes(&first.strip_timings, pins, bit_planes(&strips));
+        self.end_frame();
+    }
+}
@@ -0,0 +1,187 @@
+//! Host-side stand-ins for the hardware, so a `LightingController` can be stepped one frame at a
+//! time and its output inspected without a board attached. Time is simulated by a
+//! `VirtualClock` that only moves when it is told to, which makes every run reproducible.
+
+use crate::colors::Color16;
+use crate::hardware::{LedDriver, PeriodicTimer, TimerError};
+use crate::leds::ws28xx::PhysicalStrip;
+use crate::lighting_controller::LightingController;
+use core::cell::Cell;
+use embedded_time::duration::Nanoseconds;
+use embedded_time::fixed_point::FixedPoint;
+
+/// A nanosecond clock that only advances when asked to. Timers created from it share its time,
+/// so the test code can hold onto the clock while the timers are borrowed elsewhere.
+#[derive(Default)]
+pub struct VirtualClock {
+    now_ns: Cell<u64>,
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn now(&self) -> u64 {
+        self.now_ns.get()
+    }
+
+    pub fn advance(&self, nanos: u64) {
+        self.now_ns.set(self.now_ns.get() + nanos);
+    }
+
+    /// Moves the clock forward to `nanos`. Does nothing if that time has already passed.
+    pub fn advance_to(&self, nanos: u64) {
+        if nanos > self.now_ns.get() {
+            self.now_ns.set(nanos);
+        }
+    }
+
+    pub fn timer(&self) -> VirtualTimer<'_> {
+        VirtualTimer { clock: self, period_ns: 0, next_match_ns: 0, is_free_running: false }
+    }
+
+    /// A timer whose timeout checks always succeed, as if the caller had been spinning on them
+    /// until they did. Use this one for code that busy-waits on `periodic_check_timeout`, like
+    /// `PhysicalStrip::send_bits`, which would otherwise never see the clock move.
+    pub fn free_running_timer(&self) -> VirtualTimer<'_> {
+        VirtualTimer { clock: self, period_ns: 0, next_match_ns: 0, is_free_running: true }
+    }
+}
+
+/// A `PeriodicTimer` running off a `VirtualClock`. `periodic_check_timeout` never moves the clock,
+/// so it only times out after the clock has been advanced past the next period. `periodic_wait`
+/// stands in for a busy wait, and jumps the clock forward to the end of the current period.
+pub struct VirtualTimer<'c> {
+    clock: &'c VirtualClock,
+    period_ns: u64,
+    next_match_ns: u64,
+    is_free_running: bool,
+}
+
+impl<'c> VirtualTimer<'c> {
+    pub fn clock(&self) -> &'c VirtualClock {
+        self.clock
+    }
+}
+
+impl<'c> PeriodicTimer for VirtualTimer<'c> {
+    fn periodic_start(&mut self, time: impl Into<Nanoseconds<u64>>) {
+        let time: Nanoseconds<u64> = time.into();
+        self.period_ns = time.integer();
+        self.next_match_ns = self.clock.now() + self.period_ns;
+    }
+
+    fn periodic_wait(&mut self) {
+        self.clock.advance_to(self.next_match_ns);
+        self.next_match_ns += self.period_ns;
+    }
+
+    fn periodic_check_timeout(&mut self) -> Result<(), TimerError> {
+        if self.is_free_running {
+            self.periodic_wait();
+            return Ok(());
+        }
+        if self.clock.now() >= self.next_match_ns {
+            self.next_match_ns += self.period_ns;
+            Ok(())
+        } else {
+            Err(TimerError::WouldBlock)
+        }
+    }
+}
+
+/// A `LedDriver` that copies every frame into memory instead of sending it anywhere. Strips are
+/// stored back to back in the order they are sent, the same layout `LogicalStrip` uses for its
+/// own byte buffer.
+pub struct CaptureDriver<const N_BYTES: usize> {
+    bytes: [u8; N_BYTES],
+    cursor: usize,
+    frames_sent: usize,
+}
+
+impl<const N_BYTES: usize> Default for CaptureDriver<N_BYTES> {
+    fn default() -> Self {
+        Self { bytes: [0; N_BYTES], cursor: 0, frames_sent: 0 }
+    }
+}
+
+impl<const N_BYTES: usize> CaptureDriver<N_BYTES> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The bytes of the last complete frame.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..]
+    }
+
+    pub fn frames_sent(&self) -> usize {
+        self.frames_sent
+    }
+}
+
+impl<const N_BYTES: usize> LedDriver for CaptureDriver<N_BYTES> {
+    fn send_strip(&mut self, _: usize, _: &PhysicalStrip, bytes: &[u8]) {
+        let end = self.cursor + bytes.len();
+        self.bytes[self.cursor..end].copy_from_slice(bytes);
+        self.cursor = end;
+    }
+
+    fn end_frame(&mut self) {
+        self.cursor = 0;
+        self.frames_sent += 1;
+    }
+}
+
+/// Wraps a `LightingController` running on a `VirtualTimer` so it can be stepped frame by frame.
+/// Each step advances the clock by exactly one frame period, so exactly one frame is rendered.
+pub struct Simulator<'a, const N_ANI: usize, const N_BYTES: usize> {
+    clock: &'a VirtualClock,
+    controller: LightingController<'a, VirtualTimer<'a>, N_ANI>,
+    driver: CaptureDriver<N_BYTES>,
+    frame_period_ns: u64,
+}
+
+impl<'a, const N_ANI: usize, const N_BYTES: usize> Simulator<'a, N_ANI, N_BYTES> {
+    pub fn new(
+        clock: &'a VirtualClock,
+        controller: LightingController<'a, VirtualTimer<'a>, N_ANI>,
+    ) -> Self {
+        let frame_period_ns = 1_000_000_000_u64 / controller.frame_rate().integer() as u64;
+        Self { clock, controller, driver: CaptureDriver::new(), frame_period_ns }
+    }
+
+    /// Renders the next frame and returns the colors of every LED in the logical strip.
+    pub fn step(&mut self) -> &[Color16] {
+        self.clock.advance(self.frame_period_ns);
+        self.controller.update(&mut self.driver);
+        self.colors()
+    }
+
+    /// Renders `frames` frames, handing each one to `on_frame` along with its frame number.
+    pub fn run(&mut self, frames: usize, mut on_frame: impl FnMut(usize, &[Color16])) {
+        for frame in 0..frames {
+            on_frame(frame, self.step());
+        }
+    }
+
+    /// The colors of the most recently rendered frame.
+    pub fn colors(&self) -> &[Color16] {
+        self.controller.logical_strip().colors()
+    }
+
+    /// The encoded bytes of the most recently rendered frame, as the LEDs would receive them.
+    pub fn bytes(&self) -> &[u8] {
+        self.driver.bytes()
+    }
+
+    pub fn frames_rendered(&self) -> usize {
+        self.driver.frames_sent()
+    }
+
+    /// Gives access to the controller between frames, e.g. to fire triggers or set offsets.
+    pub fn controller(&mut self) -> &mut LightingController<'a, VirtualTimer<'a>, N_ANI> {
+        &mut self.controller
+    }
+}
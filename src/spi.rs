@@ -0,0 +1,235 @@
+//! An output backend that sends WS28xx data with an SPI peripheral instead of bit-banging a pin.
+//! Each bit the LEDs take is stretched into a symbol of 3 or 4 SPI bits, high for the first part
+//! of the symbol and low for the rest, so that once a frame is encoded the peripheral (and its
+//! DMA) can produce the whole waveform without the CPU. The symbols are worked out from the
+//! strip's `StripTimings`. Every bitstream starts with enough zero bytes to hold the line low for
+//! the LEDs' reset latch, the same time the bit-bang backend waits before sending.
+//!
+//! `encode` and `decode` are pure functions over byte slices, so the bitstream can be checked
+//! on the host without any SPI hardware.
+
+use crate::hardware::LedDriver;
+use crate::leds::ws28xx::{PhysicalStrip, StripTimings};
+use crate::waveform::{required_reset_ns, PULSE_TOLERANCE_NS};
+
+/// Everything that can go wrong when encoding or decoding an SPI bitstream.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpiError {
+    /// Only 3 and 4 bit symbols are supported.
+    UnsupportedSymbolLength { bits: u32 },
+    /// The high time of a zero or a one can't be made from whole SPI bits within
+    /// `PULSE_TOLERANCE_NS`, `nearest_ns` being the closest it can get.
+    HighOutOfSpec { high_ns: u32, nearest_ns: u32 },
+    /// Zeroes and ones came out as the same symbol, so the LEDs couldn't tell them apart.
+    AmbiguousSymbols,
+    /// There was more data than the output slice could hold.
+    TooManyBytes { capacity: usize },
+    /// A symbol was neither a zero nor a one.
+    InvalidSymbol { bit_index: usize },
+    /// The bitstream did not end on a whole byte's worth of symbols.
+    PartialByte,
+    /// The bitstream did not start with the zero bytes of the reset latch.
+    MissingReset,
+    /// The strip at `strip_index` has no entry in the encodings.
+    MissingEncoding { strip_index: usize },
+    /// The buffer can't hold the `required` encoded bytes of the strip at `strip_index`.
+    BufferTooSmall { strip_index: usize, required: usize },
+}
+
+/// The SPI symbols for a strip's zeroes and ones, and the clock to send them at.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SpiEncoding {
+    bits_per_symbol: u32,
+    zero_symbol: u8,
+    one_symbol: u8,
+    clock_hz: u32,
+    reset_len: usize,
+}
+
+impl SpiEncoding {
+    /// Works out the symbols for `timings` with `bits_per_symbol` SPI bits to each LED bit. The
+    /// SPI clock is picked so a symbol lasts exactly `full_cycle`, and the high times are
+    /// rounded to whole SPI bits, which have to land within `PULSE_TOLERANCE_NS` of the timings.
+    pub fn new(timings: &StripTimings, bits_per_symbol: u32) -> Result<Self, SpiError> {
+        if !(3..=4).contains(&bits_per_symbol) {
+            return Err(SpiError::UnsupportedSymbolLength { bits: bits_per_symbol });
+        }
+        let symbol = |high_ns: u32| {
+            let bits = (high_ns * bits_per_symbol + timings.full_cycle / 2) / timings.full_cycle;
+            let bits = bits.clamp(1, bits_per_symbol - 1);
+            let nearest_ns = bits * timings.full_cycle / bits_per_symbol;
+            match nearest_ns.abs_diff(high_ns) <= PULSE_TOLERANCE_NS {
+                true => Ok((0xFF_u8 << (8 - bits)) >> (8 - bits_per_symbol)),
+                false => Err(SpiError::HighOutOfSpec { high_ns, nearest_ns }),
+            }
+        };
+        let (zero_symbol, one_symbol) = (symbol(timings.zero_h)?, symbol(timings.one_h)?);
+        if zero_symbol == one_symbol {
+            return Err(SpiError::AmbiguousSymbols);
+        }
+        let clock_hz = (bits_per_symbol as u64 * 1_000_000_000 / timings.full_cycle as u64) as u32;
+        let reset_bits = (required_reset_ns(timings) * clock_hz as u64).div_ceil(1_000_000_000);
+        let reset_len = reset_bits.div_ceil(8) as usize;
+        Ok(Self { bits_per_symbol, zero_symbol, one_symbol, clock_hz, reset_len })
+    }
+
+    /// The SPI clock the symbols have to be sent at.
+    pub fn clock_hz(&self) -> u32 {
+        self.clock_hz
+    }
+
+    pub fn bits_per_symbol(&self) -> u32 {
+        self.bits_per_symbol
+    }
+
+    /// How many zero bytes the bitstream starts with, holding the line low long enough for the
+    /// LEDs to latch the last frame.
+    pub fn reset_len(&self) -> usize {
+        self.reset_len
+    }
+
+    /// How many SPI bytes `byte_count` LED bytes take. Each LED byte is 8 symbols, so this is
+    /// one SPI byte for every bit in a symbol, after the reset.
+    pub const fn encoded_len(&self, byte_count: usize) -> usize {
+        self.reset_len + byte_count * self.bits_per_symbol as usize
+    }
+}
+
+/// Encodes `bytes` into the SPI bitstream for `encoding`, most significant bit first, and
+/// returns how many bytes of `out` it used. The symbols follow `reset_len` zero bytes, and every
+/// symbol ends low, so the line is also left low once the transfer is over.
+pub fn encode(encoding: &SpiEncoding, bytes: &[u8], out: &mut [u8]) -> Result<usize, SpiError> {
+    let len = encoding.encoded_len(bytes.len());
+    if len > out.len() {
+        return Err(SpiError::TooManyBytes { capacity: out.len() });
+    }
+    let (reset, symbols) = out[..len].split_at_mut(encoding.reset_len);
+    reset.fill(0);
+    let n = encoding.bits_per_symbol as usize;
+    for (byte, chunk) in bytes.iter().zip(symbols.chunks_exact_mut(n)) {
+        let mut symbols = 0_u32;
+        for bit in (0..8).rev() {
+            let symbol = match (byte >> bit) & 1 {
+                1 => encoding.one_symbol,
+                _ => encoding.zero_symbol,
+            };
+            symbols = (symbols << n) | symbol as u32;
+        }
+        chunk.copy_from_slice(&symbols.to_be_bytes()[4 - n..]);
+    }
+    Ok(len)
+}
+
+/// Turns an SPI bitstream made by `encode` back into the LED bytes it carries, returning how
+/// many bytes of `out` it filled.
+pub fn decode(encoding: &SpiEncoding, data: &[u8], out: &mut [u8]) -> Result<usize, SpiError> {
+    let data = match data.split_at_checked(encoding.reset_len) {
+        Some((reset, data)) if reset.iter().all(|&byte| byte == 0) => data,
+        _ => return Err(SpiError::MissingReset),
+    };
+    let n = encoding.bits_per_symbol as usize;
+    if !data.len().is_multiple_of(n) {
+        return Err(SpiError::PartialByte);
+    }
+    let len = data.len() / n;
+    if len > out.len() {
+        return Err(SpiError::TooManyBytes { capacity: out.len() });
+    }
+    let mask = (1_u32 << n) - 1;
+    for (byte_index, (chunk, byte)) in data.chunks_exact(n).zip(out.iter_mut()).enumerate() {
+        let mut symbols = [0; 4];
+        symbols[4 - n..].copy_from_slice(chunk);
+        let symbols = u32::from_be_bytes(symbols);
+        *byte = 0;
+        for bit in 0..8 {
+            let symbol = ((symbols >> ((7 - bit) * n)) & mask) as u8;
+            let is_one = match symbol {
+                s if s == encoding.one_symbol => true,
+                s if s == encoding.zero_symbol => false,
+                _ => return Err(SpiError::InvalidSymbol { bit_index: byte_index * 8 + bit }),
+            };
+            *byte = (*byte << 1) | is_one as u8;
+        }
+    }
+    Ok(len)
+}
+
+/// Something that can send an encoded bitstream out of an SPI peripheral, typically by handing
+/// it to DMA. Each strip is its own output, like the pins of the bit-bang backend.
+pub trait SpiOutput {
+    /// Starts sending `data` for the strip at `strip_index` with the clock at `clock_hz`. The
+    /// data is only valid until the next call, so an output using DMA has to wait for the last
+    /// transfer to finish, or copy the data, before returning.
+    fn write(&mut self, strip_index: usize, clock_hz: u32, data: &[u8]);
+
+    /// Called once all the strips of a frame have been written.
+    fn flush(&mut self) {}
+}
+
+/// The SPI backend: encodes each strip's bytes into `buffer` and hands them to the
+/// `SpiOutput`. WS28xx strips are encoded into symbols with that strip's encoding, while clocked
+/// strips are framed by their `Protocol` and sent at its clock. The buffer needs room for the
+/// encoded bytes of the longest strip.
+pub struct SpiController<'a, O> {
+    output: O,
+    encodings: &'a [SpiEncoding],
+    buffer: &'a mut [u8],
+}
+
+impl<'a, O: SpiOutput> SpiController<'a, O> {
+    /// `encodings` has one entry for each of `strips`, which are the `LogicalStrip`'s strips in
+    /// the same order. The entries of clocked strips aren't used. Returns an error if an entry
+    /// is missing, or if `buffer` is too small for any of the strips once encoded.
+    pub fn new(
+        output: O,
+        strips: &[PhysicalStrip],
+        encodings: &'a [SpiEncoding],
+        buffer: &'a mut [u8],
+    ) -> Result<Self, SpiError> {
+        for (strip_index, strip) in strips.iter().enumerate() {
+            let encoding = encodings
+                .get(strip_index)
+                .ok_or(SpiError::MissingEncoding { strip_index })?;
+            let byte_count = strip.led_count * strip.pixel_format.bytes_per_led();
+            let required = match strip.protocol.clock_hz() {
+                Some(_) => strip.protocol.encoded_len(byte_count),
+                None => encoding.encoded_len(byte_count),
+            };
+            if required > buffer.len() {
+                return Err(SpiError::BufferTooSmall { strip_index, required });
+            }
+        }
+        Ok(Self { output, encodings, buffer })
+    }
+
+    pub fn output(&mut self) -> &mut O {
+        &mut self.output
+    }
+}
+
+/// Clocked strips with RGBW pixels can't be framed, so they are skipped and their LEDs keep
+/// whatever they last showed.
+impl<'a, O: SpiOutput> LedDriver for SpiController<'a, O> {
+    fn send_strip(&mut self, strip_index: usize, strip: &PhysicalStrip, bytes: &[u8]) {
+        // new checked that every strip fits in the buffer, so only clocked RGBW strips fail here:
+        let encoded = match strip.protocol.clock_hz() {
+            Some(clock_hz) => strip
+                .encode(bytes, self.buffer)
+                .map(|len| (clock_hz, len))
+                .ok(),
+            None => {
+                let encoding = &self.encodings[strip_index];
+                let len = encode(encoding, bytes, self.buffer).ok();
+                len.map(|len| (encoding.clock_hz(), len))
+            }
+        };
+        if let Some((clock_hz, len)) = encoded {
+            self.output
+                .write(strip_index, clock_hz, &self.buffer[..len]);
+        }
+    }
+
+    fn end_frame(&mut self) {
+        self.output.flush();
+    }
+}
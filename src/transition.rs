@@ -0,0 +1,125 @@
+use crate::animations::{Animatable, Direction};
+use crate::colors::{self, Color16};
+use crate::utility::{AnimationRng, Progression};
+use arrayvec::ArrayVec;
+use bitvec::prelude::*;
+use rand::{Rng, SeedableRng};
+
+/// Adjust MAX_NUM_* consts depending on RAM requirements. Replacing an animation that has more
+/// LEDs than this, or with one that does, cuts straight over instead of transitioning:
+pub const MAX_NUM_TRANSITION_LEDS: usize = 512;
+
+/// How the controller moves from one animation to the next when one is replaced.
+#[derive(Default, Copy, Clone)]
+pub enum Transition {
+    /// Swaps to the new animation on the next frame, ignoring the duration.
+    #[default]
+    Cut,
+    /// Fades every LED from the old animation to the new one together.
+    Crossfade,
+    /// Sweeps the new animation over the old one. A positive wipe starts at the first LED of the
+    /// translation array, a negative one at the last. A stopped wipe doesn't move, so it
+    /// crossfades instead.
+    Wipe(Direction),
+    /// Switches the LEDs over one at a time in a random order.
+    Dissolve,
+    /// Fades the old animation out to black over the first half of the duration, then fades the
+    /// new one in over the second half.
+    FadeThroughBlack,
+}
+
+/// A transition in progress. It holds on to the animation being replaced so that both can be
+/// rendered until the transition has finished.
+pub struct ActiveTransition<'a> {
+    pub outgoing: &'a mut dyn Animatable<'a>,
+    transition: Transition,
+    frames: Progression,
+    seed: u64,
+    // the two translation arrays can put the same LED at different positions, so these match
+    // them up once rather than searching every frame. For each position in the incoming
+    // animation, the position of the same LED in the outgoing one:
+    outgoing_positions: ArrayVec<Option<u16>, MAX_NUM_TRANSITION_LEDS>,
+    // and the positions in the outgoing animation whose LED the incoming one also covers:
+    covered: BitArr!(for MAX_NUM_TRANSITION_LEDS),
+}
+
+impl<'a> ActiveTransition<'a> {
+    /// Returns None if either animation has more than MAX_NUM_TRANSITION_LEDS LEDs.
+    pub fn new(
+        outgoing: &'a mut dyn Animatable<'a>,
+        incoming: &dyn Animatable<'a>,
+        transition: Transition,
+        frames: usize,
+        seed: u64,
+    ) -> Option<Self> {
+        let (outgoing_leds, incoming_leds) =
+            (outgoing.translation_array(), incoming.translation_array());
+        if outgoing_leds.len().max(incoming_leds.len()) > MAX_NUM_TRANSITION_LEDS {
+            return None;
+        }
+
+        let mut outgoing_positions = ArrayVec::new();
+        let mut covered = BitArray::zeroed();
+        for index in incoming_leds {
+            let position = outgoing_leds.iter().position(|led| led == index);
+            if let Some(position) = position {
+                covered.set(position, true);
+            }
+            outgoing_positions.push(position.map(|position| position as u16));
+        }
+        let frames = Progression::new(frames);
+        Some(Self { outgoing, transition, frames, seed, outgoing_positions, covered })
+    }
+
+    /// The position in the outgoing animation of the LED at `position` in the incoming one, if
+    /// the outgoing animation covers it.
+    pub fn outgoing_position(&self, position: usize) -> Option<usize> {
+        self.outgoing_positions[position].map(usize::from)
+    }
+
+    /// Returns true if the incoming animation covers the LED at `position` in the outgoing one.
+    pub fn is_covered(&self, position: usize) -> bool {
+        self.covered[position]
+    }
+
+    /// Moves on to the next frame, returning true once the transition has finished.
+    pub fn advance(&mut self) -> bool {
+        self.frames.checked_increment()
+    }
+
+    /// Mixes the outgoing color `from` and the incoming color `to` for the LED at `position` in a
+    /// segment `led_count` long. The last frame of the transition is entirely the new color.
+    pub fn mix(&self, position: usize, led_count: usize, from: Color16, to: Color16) -> Color16 {
+        let (step, total) = (self.frames.get_current() as i32 + 1, self.frames.total as i32);
+        match self.transition {
+            Transition::Cut => to,
+            Transition::Crossfade | Transition::Wipe(Direction::Stopped) => {
+                Color16::color_lerp(step, 0, total, from, to)
+            }
+            Transition::Wipe(direction) => {
+                let position = match direction {
+                    Direction::Negative => led_count - 1 - position,
+                    _ => position,
+                } as i32;
+                // the edge crosses one LED more than the segment, so the last one is finished in
+                // time:
+                let edge = step * (led_count as i32 + 1) - position * total;
+                Color16::color_lerp(edge.clamp(0, total), 0, total, from, to)
+            }
+            Transition::Dissolve => {
+                let mut rng = AnimationRng::seed_from_u64(self.seed ^ position as u64);
+                match step > rng.gen_range(0..total) {
+                    true => to,
+                    false => from,
+                }
+            }
+            Transition::FadeThroughBlack => {
+                let off = colors::C_OFF.into();
+                match 2 * step <= total {
+                    true => Color16::color_lerp(2 * step, 0, total, from, off),
+                    false => Color16::color_lerp(2 * step - total, 0, total, off, to),
+                }
+            }
+        }
+    }
+}
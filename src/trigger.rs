@@ -1,15 +1,16 @@
-use crate::animations::{Direction, MAX_OFFSET};
+use crate::animations::{Direction, Mask, MAX_OFFSET};
 use crate::colors;
-use crate::colors::Color;
+use crate::colors::{Color16, Interpolation, LayerBlend};
 use crate::utility::{
-    convert_ns_to_frames, get_random_offset, shift_offset, FadeRainbow, MarchingRainbow,
-    MarchingRainbowMut, Progression, StatefulRainbow, TimedRainbows,
+    convert_ns_to_frames, get_random_offset, shift_offset, AnimationRng, FadeRainbow,
+    MarchingRainbow, MarchingRainbowMut, Progression, StatefulRainbow, TimedRainbows,
 };
 use arrayvec::ArrayVec;
 use embedded_time::rate::Hertz;
+use rand::SeedableRng;
 
 pub type TriggerInit = fn(&mut Trigger, &mut TimedRainbows);
-pub type TriggerUpdater = fn(&mut Trigger, &mut [Color]);
+pub type TriggerUpdater = fn(&mut Trigger, &mut [Color16]);
 pub type TriggerBehavior = (Option<TriggerInit>, Option<TriggerUpdater>);
 
 /// These are the types of triggered animation effects that are possible with an animation. They can
@@ -98,11 +99,15 @@ impl Mode {
     }
 }
 
-/// All triggers share a single rainbow / fade speed, which is configured in this struct
+/// All triggers share a single rainbow / fade speed, which is configured in this struct. They
+/// are also drawn as one layer, so they share a blend mode, opacity and mask too.
 pub struct GlobalParameters<'a> {
-    pub rainbow: colors::Rainbow<'a>,
+    pub rainbow: colors::Palette<'a>,
     pub is_rainbow_forward: bool,
     pub duration_ns: u64,
+    pub interpolation: Interpolation,
+    pub blend: LayerBlend,
+    pub mask: Mask<'a>,
 }
 
 /// This holds all triggers and contains the variables that apply to all triggers simultaneously, and not just to
@@ -111,22 +116,43 @@ pub struct TriggerCollection<'a, const N: usize> {
     pub fade_rainbow: StatefulRainbow<'a>,
     pub incremental_rainbow: StatefulRainbow<'a>,
     pub frames: Progression,
+    pub rng: AnimationRng,
+    pub blend: LayerBlend,
+    pub mask: Mask<'a>,
+    interpolation: Interpolation,
     triggers: ArrayVec<Trigger, N>,
 }
 
 impl<'a, const N: usize> TriggerCollection<'a, N> {
-    pub fn new(init: &GlobalParameters<'a>, frame_rate: Hertz) -> Self {
+    pub fn new(init: &GlobalParameters<'a>, frame_rate: Hertz, seed: u64) -> Self {
         let fade_rainbow = StatefulRainbow::new(init.rainbow, init.is_rainbow_forward);
         let incremental_rainbow = StatefulRainbow::new(init.rainbow, init.is_rainbow_forward);
         let frames = Progression::new(convert_ns_to_frames(init.duration_ns, frame_rate));
+        let rng = AnimationRng::seed_from_u64(seed);
+        let (blend, mask) = (init.blend, init.mask);
+        let interpolation = init.interpolation;
         let triggers = ArrayVec::new();
 
-        Self { fade_rainbow, incremental_rainbow, frames, triggers }
+        Self {
+            fade_rainbow,
+            incremental_rainbow,
+            frames,
+            rng,
+            blend,
+            mask,
+            interpolation,
+            triggers,
+        }
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = AnimationRng::seed_from_u64(seed);
     }
 
     pub fn add_trigger(&mut self, init: &Parameters, frame_rate: Hertz) {
         let (initializer, updater) = init.mode.get_behavior();
-        let mut new_trigger = Trigger::new(init, self.current_rainbow_color(), frame_rate);
+        let color = self.current_rainbow_color().into();
+        let mut new_trigger = Trigger::new(init, color, frame_rate);
 
         if let Some(initialize) = initializer {
             initialize(
@@ -135,15 +161,18 @@ impl<'a, const N: usize> TriggerCollection<'a, N> {
                     fade_rainbow: &mut self.fade_rainbow,
                     incremental_rainbow: &mut self.incremental_rainbow,
                     frames: &mut self.frames,
+                    rng: &mut self.rng,
+                    interpolation: self.interpolation,
                 },
             );
         }
         new_trigger.updater = updater;
+        new_trigger.interpolation = self.interpolation;
 
         let _ = self.triggers.try_push(new_trigger);
     }
 
-    pub fn update(&mut self, segment: &mut [Color]) {
+    pub fn update(&mut self, segment: &mut [Color16]) {
         for trigger in self.triggers.iter_mut() {
             trigger.update(segment)
         }
@@ -177,13 +206,14 @@ pub struct Trigger {
     frames: Progression,
     transition_frame: usize,
     direction: Direction,
-    color: Color,
+    color: Color16,
+    interpolation: Interpolation,
     updater: Option<TriggerUpdater>,
     pixels_per_pixel_group: usize,
 }
 
 impl Trigger {
-    pub fn new(init: &Parameters, color: Color, frame_rate: Hertz) -> Self {
+    pub fn new(init: &Parameters, color: Color16, frame_rate: Hertz) -> Self {
         let offset = init.starting_offset;
         let total_duration_ns = init.fade_in_time_ns + init.fade_out_time_ns;
 
@@ -192,14 +222,24 @@ impl Trigger {
 
         let transition_frame = convert_ns_to_frames(init.fade_in_time_ns, frame_rate);
         let direction = init.direction;
+        let interpolation = Interpolation::default();
         let updater = None;
 
         let pixels_per_pixel_group = init.pixels_per_pixel_group;
 
-        Self { offset, frames, transition_frame, direction, color, updater, pixels_per_pixel_group }
+        Self {
+            offset,
+            frames,
+            transition_frame,
+            direction,
+            color,
+            interpolation,
+            updater,
+            pixels_per_pixel_group,
+        }
     }
 
-    pub fn update(&mut self, segment: &mut [Color]) {
+    pub fn update(&mut self, segment: &mut [Color16]) {
         if let Some(f) = self.updater {
             f(self, segment);
         }
@@ -233,15 +273,15 @@ fn get_trigger_fade_progress(trigger: &mut Trigger) -> Progression {
     progress
 }
 
-fn flash(trigger: &mut Trigger, segment: &mut [Color]) {
+fn flash(trigger: &mut Trigger, segment: &mut [Color16]) {
     let progress = get_trigger_fade_progress(trigger);
 
     for led in segment {
-        *led = led.lerp_with(trigger.color, progress);
+        *led = led.lerp_with_in(trigger.color, progress, trigger.interpolation);
     }
 }
 
-fn color_pulse(trigger: &mut Trigger, segment: &mut [Color]) {
+fn color_pulse(trigger: &mut Trigger, segment: &mut [Color16]) {
     let progress = get_trigger_fade_progress(trigger);
 
     // the range will be always at least 1 led, up to pixels_per_pixel_group leds:
@@ -251,11 +291,12 @@ fn color_pulse(trigger: &mut Trigger, segment: &mut [Color]) {
 
     for index in first_led_index..last_led_index {
         let corrected_index = index % segment.len();
-        segment[corrected_index] = segment[corrected_index].lerp_with(trigger.color, progress);
+        let led = &mut segment[corrected_index];
+        *led = led.lerp_with_in(trigger.color, progress, trigger.interpolation);
     }
 }
 
-fn color_shot(trigger: &mut Trigger, segment: &mut [Color]) {
+fn color_shot(trigger: &mut Trigger, segment: &mut [Color16]) {
     let current_offset = shift_offset(trigger.offset, trigger.frames, trigger.direction) as usize;
     let offset_distance_between_leds = MAX_OFFSET as usize / segment.len();
 
@@ -270,9 +311,9 @@ fn color_shot(trigger: &mut Trigger, segment: &mut [Color]) {
     }
 }
 
-fn init_color_pulse(trigger: &mut Trigger, _: &mut TimedRainbows) {
+fn init_color_pulse(trigger: &mut Trigger, global: &mut TimedRainbows) {
     trigger.direction = Direction::Stopped;
-    trigger.offset = get_random_offset();
+    trigger.offset = get_random_offset(global.rng);
 }
 
 fn init_color_pulse_fade(trigger: &mut Trigger, global: &mut TimedRainbows) {
@@ -281,7 +322,7 @@ fn init_color_pulse_fade(trigger: &mut Trigger, global: &mut TimedRainbows) {
 }
 
 fn init_color_pulse_rainbow(trigger: &mut Trigger, global: &mut TimedRainbows) {
-    trigger.color = global.current_rainbow_color();
+    trigger.color = global.current_rainbow_color().into();
     init_color_pulse(trigger, global);
     global.advance_rainbow_color();
 }
@@ -296,7 +337,7 @@ fn init_color_shot_fade(trigger: &mut Trigger, global: &mut TimedRainbows) {
 }
 
 fn init_color_shot_rainbow(trigger: &mut Trigger, global: &mut TimedRainbows) {
-    trigger.color = global.current_rainbow_color();
+    trigger.color = global.current_rainbow_color().into();
     init_color_shot(trigger, global);
     global.advance_rainbow_color();
 }
@@ -312,6 +353,6 @@ fn init_flash_fade(trigger: &mut Trigger, global: &mut TimedRainbows) {
 
 fn init_flash_rainbow(trigger: &mut Trigger, global: &mut TimedRainbows) {
     init_flash(trigger, global);
-    trigger.color = global.current_rainbow_color();
+    trigger.color = global.current_rainbow_color().into();
     global.advance_rainbow_color();
 }
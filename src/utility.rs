@@ -2,12 +2,17 @@ use core::ops::Index;
 
 use crate::{
     animations::{Direction, MAX_OFFSET},
-    colors::{Color, Rainbow},
+    colors::{Blend, Color, Color16, Interpolation, Palette},
 };
 
+use embedded_time::rate::*;
+use rand::{rngs::SmallRng, Rng};
+
+#[cfg(feature = "bl602")]
 use bl602_hal as hal;
+#[cfg(feature = "bl602")]
 use core::fmt::Write;
-use embedded_time::rate::*;
+#[cfg(feature = "bl602")]
 use hal::{
     clock::{Clocks, Strict, SysclkFreq, UART_PLL_FREQ},
     gpio::*,
@@ -16,6 +21,7 @@ use hal::{
     timer::*,
 };
 
+#[cfg(feature = "bl602")]
 pub fn init_clocks(config: &mut ClkCfg) -> Clocks {
     Strict::new()
         .use_pll(40_000_000u32.Hz())
@@ -24,6 +30,7 @@ pub fn init_clocks(config: &mut ClkCfg) -> Clocks {
         .freeze(config)
 }
 
+#[cfg(feature = "bl602")]
 pub fn init_timers(
     timer: pac::TIMER,
     clocks: &Clocks,
@@ -40,6 +47,7 @@ pub fn init_timers(
     (timer_ch0, timer_ch1)
 }
 
+#[cfg(feature = "bl602")]
 pub fn init_usb_serial<MODE>(
     uart: pac::UART,
     clocks: Clocks,
@@ -75,8 +83,26 @@ pub fn default_translation_array<const SIZE: usize>(start_at: usize) -> [usize;
     result
 }
 
-pub fn get_random_offset() -> u16 {
-    riscv::register::mcycle::read64() as u16
+/// The random number generator used by animations for random offsets and any other random
+/// effects. Each animation owns its own, so it can be seeded to make its output reproducible.
+pub type AnimationRng = SmallRng;
+
+/// The seed used for animations that are not given one explicitly. On the BL602 this samples the
+/// cycle counter, so each boot gets a different sequence.
+#[cfg(feature = "bl602")]
+pub fn default_seed() -> u64 {
+    riscv::register::mcycle::read64()
+}
+
+/// The seed used for animations that are not given one explicitly. Host builds have no cycle
+/// counter to sample, so they always start from the same seed.
+#[cfg(not(feature = "bl602"))]
+pub fn default_seed() -> u64 {
+    0xB602_2811
+}
+
+pub fn get_random_offset(rng: &mut AnimationRng) -> u16 {
+    rng.gen()
 }
 
 pub fn shift_offset(starting_offset: u16, frames: Progression, direction: Direction) -> u16 {
@@ -94,7 +120,7 @@ pub fn shift_offset(starting_offset: u16, frames: Progression, direction: Direct
 }
 
 pub struct ReversibleRainbow<'a> {
-    backer: Rainbow<'a>,
+    backer: Palette<'a>,
     is_forward: bool,
 }
 
@@ -106,6 +132,28 @@ impl<'a> ReversibleRainbow<'a> {
     pub fn is_empty(&self) -> bool {
         self.backer.is_empty()
     }
+
+    pub fn palette(&self) -> Palette<'a> {
+        self.backer
+    }
+
+    pub fn is_forward(&self) -> bool {
+        self.is_forward
+    }
+
+    /// The blend style between the color at `index` and the one after it. When reversed this is
+    /// the blend of the same pair of stops, just travelled in the other direction.
+    pub fn blend(&self, index: usize) -> Blend {
+        let len = self.backer.len();
+        if len == 0 {
+            return Blend::Linear;
+        }
+        let index = index % len;
+        match self.is_forward {
+            true => self.backer.blend(index),
+            false => self.backer.blend((2 * len - 2 - index) % len),
+        }
+    }
 }
 
 impl<'a> Index<usize> for ReversibleRainbow<'a> {
@@ -113,8 +161,8 @@ impl<'a> Index<usize> for ReversibleRainbow<'a> {
 
     fn index(&self, index: usize) -> &Self::Output {
         match self.is_forward {
-            true => &self.backer[index],
-            false => &self.backer[self.backer.len() - 1 - index],
+            true => self.backer.color(index),
+            false => self.backer.color(self.backer.len() - 1 - index),
         }
     }
 }
@@ -122,16 +170,21 @@ impl<'a> Index<usize> for ReversibleRainbow<'a> {
 pub trait FadeRainbow {
     fn rainbow(&self) -> &StatefulRainbow;
     fn frames(&self) -> &Progression;
+    fn interpolation(&self) -> Interpolation;
 
-    fn calculate_fade_color(&self) -> Color {
+    /// Fades from the current color to the next over `frames`, shaped by the current color's
+    /// blend. Every color gets the same number of frames, so gradient stop positions are ignored.
+    fn calculate_fade_color(&self) -> Color16 {
         let (rainbow, frames) = (self.rainbow(), self.frames());
 
-        let current_color = rainbow.current_color();
+        let current_color = rainbow.current_color().into();
         if frames.total == 0 {
             return current_color;
         }
-        let next_color = rainbow.peek_next_color();
-        current_color.lerp_with(next_color, *frames)
+        let next_color = rainbow.peek_next_color().into();
+        let (current, total) = (frames.get_current() as i32, frames.total as i32);
+        let factor = rainbow.current_blend().shape(current, total);
+        Color16::color_lerp_in(self.interpolation(), factor, 0, total, current_color, next_color)
     }
 
     fn current_fade_color(&self) -> Color {
@@ -148,8 +201,8 @@ pub trait MarchingRainbow {
     }
 }
 
-pub trait MarchingRainbowMut {
-    fn rainbow_mut(&mut self) -> &mut StatefulRainbow;
+pub trait MarchingRainbowMut<'a> {
+    fn rainbow_mut(&mut self) -> &mut StatefulRainbow<'a>;
     fn frames_mut(&mut self) -> &mut Progression;
 
     /// Advances the rainbow color and resets the frame count
@@ -163,6 +216,8 @@ pub struct TimedRainbows<'a, 'b> {
     pub fade_rainbow: &'b mut StatefulRainbow<'a>,
     pub incremental_rainbow: &'b mut StatefulRainbow<'a>,
     pub frames: &'b mut Progression,
+    pub rng: &'b mut AnimationRng,
+    pub interpolation: Interpolation,
 }
 
 impl<'a, 'b> FadeRainbow for TimedRainbows<'a, 'b> {
@@ -172,6 +227,9 @@ impl<'a, 'b> FadeRainbow for TimedRainbows<'a, 'b> {
     fn frames(&self) -> &Progression {
         self.frames
     }
+    fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
 }
 
 impl<'a, 'b> MarchingRainbow for TimedRainbows<'a, 'b> {
@@ -183,8 +241,8 @@ impl<'a, 'b> MarchingRainbow for TimedRainbows<'a, 'b> {
     }
 }
 
-impl<'a, 'b> MarchingRainbowMut for TimedRainbows<'a, 'b> {
-    fn rainbow_mut(&mut self) -> &'a mut StatefulRainbow {
+impl<'a, 'b> MarchingRainbowMut<'a> for TimedRainbows<'a, 'b> {
+    fn rainbow_mut(&mut self) -> &mut StatefulRainbow<'a> {
         self.incremental_rainbow
     }
     fn frames_mut(&mut self) -> &mut Progression {
@@ -198,7 +256,7 @@ pub struct StatefulRainbow<'a> {
 }
 
 impl<'a> StatefulRainbow<'a> {
-    pub fn new(rainbow: &'a [Color], is_forward: bool) -> StatefulRainbow<'a> {
+    pub fn new(rainbow: Palette<'a>, is_forward: bool) -> StatefulRainbow<'a> {
         let position = Progression::new(rainbow.len());
         let backer = ReversibleRainbow { backer: rainbow, is_forward };
         Self { backer, position }
@@ -216,6 +274,11 @@ impl<'a> StatefulRainbow<'a> {
         self.position.increment();
     }
 
+    /// How the current color blends into the next one.
+    pub fn current_blend(&self) -> Blend {
+        self.backer.blend(self.position.get_current())
+    }
+
     pub fn peek_next_color(&self) -> Color {
         self.backer[self.position.peek_next() as usize]
     }
@@ -332,4 +395,30 @@ impl Color {
     pub fn lerp_with(&self, to_color: Color, factor: Progression) -> Color {
         Color::color_lerp(factor.get_current() as i32, 0, factor.total as i32, *self, to_color)
     }
+
+    pub fn lerp_with_in(
+        &self,
+        to_color: Color,
+        factor: Progression,
+        interpolation: Interpolation,
+    ) -> Color {
+        let (current, total) = (factor.get_current() as i32, factor.total as i32);
+        Color::color_lerp_in(interpolation, current, 0, total, *self, to_color)
+    }
+}
+
+impl Color16 {
+    pub fn lerp_with(&self, to_color: Color16, factor: Progression) -> Color16 {
+        Color16::color_lerp(factor.get_current() as i32, 0, factor.total as i32, *self, to_color)
+    }
+
+    pub fn lerp_with_in(
+        &self,
+        to_color: Color16,
+        factor: Progression,
+        interpolation: Interpolation,
+    ) -> Color16 {
+        let (current, total) = (factor.get_current() as i32, factor.total as i32);
+        Color16::color_lerp_in(interpolation, current, 0, total, *self, to_color)
+    }
 }
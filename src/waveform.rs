@@ -0,0 +1,237 @@
+//! Tools for checking the waveform `PhysicalStrip::send_bits` puts on a pin. A `RecordingPin`
+//! logs every `set_high`/`set_low` against a `VirtualClock`, and `decode` turns that log back into
+//! bytes while checking each pulse against the strip's `StripTimings`.
+
+use crate::colors::Color;
+use crate::leds::ws28xx::{LogicalStrip, PhysicalStrip, StripTimings};
+use crate::simulator::VirtualClock;
+use arrayvec::ArrayVec;
+use core::convert::Infallible;
+use embedded_hal::digital::blocking::OutputPin;
+
+/// How far a pulse may stray from its nominal length and still be in spec. The WS2811 and
+/// WS2812 datasheets both allow +/-150ns on every high and low time.
+pub const PULSE_TOLERANCE_NS: u32 = 150;
+
+/// Only this many violations are kept in a `WaveformReport`, the rest are just counted.
+pub const MAX_REPORTED_VIOLATIONS: usize = 32;
+
+/// A single write to a pin, and the virtual time it happened at.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Edge {
+    pub time_ns: u64,
+    pub is_high: bool,
+}
+
+/// An `OutputPin` that records every write made to it instead of driving anything. Each write
+/// can optionally cost some virtual time, to model how long the real GPIO write takes.
+pub struct RecordingPin<'c, const N_EDGES: usize> {
+    clock: &'c VirtualClock,
+    write_latency_ns: u64,
+    edges: ArrayVec<Edge, N_EDGES>,
+    has_overflowed: bool,
+}
+
+impl<'c, const N_EDGES: usize> RecordingPin<'c, N_EDGES> {
+    pub fn new(clock: &'c VirtualClock, write_latency_ns: u64) -> Self {
+        Self { clock, write_latency_ns, edges: ArrayVec::new(), has_overflowed: false }
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges[..]
+    }
+
+    /// True if more writes were made than the pin had room to record.
+    pub fn has_overflowed(&self) -> bool {
+        self.has_overflowed
+    }
+
+    pub fn clear(&mut self) {
+        self.edges.clear();
+        self.has_overflowed = false;
+    }
+
+    fn record(&mut self, is_high: bool) {
+        self.clock.advance(self.write_latency_ns);
+        let edge = Edge { time_ns: self.clock.now(), is_high };
+        if self.edges.try_push(edge).is_err() {
+            self.has_overflowed = true;
+        }
+    }
+}
+
+impl<'c, const N_EDGES: usize> OutputPin for RecordingPin<'c, N_EDGES> {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.record(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.record(true);
+        Ok(())
+    }
+}
+
+/// Everything `decode` found wrong with a waveform.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// The pin was not held low for long enough before the first bit for the LEDs to latch.
+    ResetTooShort { low_ns: u64, required_ns: u64 },
+    /// The high part of a bit matched neither `zero_h` nor `one_h`. The bit was decoded as
+    /// whichever one it was closer to.
+    HighOutOfSpec { bit_index: usize, high_ns: u64 },
+    /// The time from the start of one bit to the start of the next did not match `full_cycle`.
+    PeriodOutOfSpec { bit_index: usize, period_ns: u64 },
+    /// The waveform did not end on a whole byte.
+    PartialByte { bit_count: usize },
+}
+
+/// The result of decoding a recorded waveform.
+pub struct WaveformReport<const N_BYTES: usize> {
+    pub bytes: ArrayVec<u8, N_BYTES>,
+    pub bit_count: usize,
+    pub violations: ArrayVec<Violation, MAX_REPORTED_VIOLATIONS>,
+    pub violation_count: usize,
+}
+
+impl<const N_BYTES: usize> WaveformReport<N_BYTES> {
+    pub fn is_in_spec(&self) -> bool {
+        self.violation_count == 0
+    }
+
+    fn report(&mut self, violation: Violation) {
+        self.violation_count += 1;
+        let _ = self.violations.try_push(violation);
+    }
+}
+
+/// Rebuilds the byte stream from the edges recorded while a single strip was sent, checking
+/// every pulse along the way. The recording is expected to start with the pin being pulled low
+/// for the reset latch, the way `send_bits` does it.
+pub fn decode<const N_BYTES: usize>(
+    edges: &[Edge],
+    timings: &StripTimings,
+) -> WaveformReport<N_BYTES> {
+    let mut report = WaveformReport {
+        bytes: ArrayVec::new(),
+        bit_count: 0,
+        violations: ArrayVec::new(),
+        violation_count: 0,
+    };
+
+    // Writes that don't change the level of the pin aren't visible on the wire:
+    let mut level = None;
+    let mut transitions = edges
+        .iter()
+        .filter(|e| {
+            let is_transition = level != Some(e.is_high);
+            level = Some(e.is_high);
+            is_transition
+        })
+        .peekable();
+
+    let required_ns = required_reset_ns(timings);
+    let reset_start = transitions.next_if(|e| !e.is_high).map(|e| e.time_ns);
+    if reset_start.is_none() {
+        report.report(Violation::ResetTooShort { low_ns: 0, required_ns });
+    }
+
+    let tolerance = PULSE_TOLERANCE_NS as u64;
+    let full_cycle = timings.full_cycle as u64;
+    let (zero_h, one_h) = (timings.zero_h as u64, timings.one_h as u64);
+    let mut current_byte = 0_u8;
+    let mut last_rise: Option<u64> = None;
+
+    while let (Some(rise), Some(fall)) = (transitions.next(), transitions.next()) {
+        match (last_rise, reset_start) {
+            (Some(last_rise), _) => {
+                let period_ns = rise.time_ns - last_rise;
+                if period_ns.abs_diff(full_cycle) > tolerance {
+                    let bit_index = report.bit_count - 1;
+                    report.report(Violation::PeriodOutOfSpec { bit_index, period_ns });
+                }
+            }
+            (None, Some(reset_start)) => {
+                let low_ns = rise.time_ns - reset_start;
+                if low_ns < required_ns {
+                    report.report(Violation::ResetTooShort { low_ns, required_ns });
+                }
+            }
+            (None, None) => {}
+        }
+        last_rise = Some(rise.time_ns);
+
+        let high_ns = fall.time_ns - rise.time_ns;
+        let is_one = high_ns.abs_diff(one_h) < high_ns.abs_diff(zero_h);
+        let nominal = if is_one { one_h } else { zero_h };
+        if high_ns.abs_diff(nominal) > tolerance {
+            let bit_index = report.bit_count;
+            report.report(Violation::HighOutOfSpec { bit_index, high_ns });
+        }
+
+        current_byte = (current_byte << 1) | is_one as u8;
+        report.bit_count += 1;
+        if report.bit_count % 8 == 0 {
+            let _ = report.bytes.try_push(current_byte);
+            current_byte = 0;
+        }
+    }
+
+    if report.bit_count % 8 != 0 {
+        report.report(Violation::PartialByte { bit_count: report.bit_count });
+    }
+    report
+}
+
+/// How long the pin has to be held low before sending for the LEDs to latch the previous frame,
+/// which is the datasheet's reset time rather than however long `send_bits` happens to wait.
+pub fn required_reset_ns(timings: &StripTimings) -> u64 {
+    timings.reset as u64
+}
+
+/// The first byte where a decoded waveform and the bytes it should have carried disagree.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ByteMismatch {
+    pub index: usize,
+    pub expected: Option<u8>,
+    pub decoded: Option<u8>,
+}
+
+/// Checks the bytes decoded from a strip's waveform against what the `LogicalStrip` had in its
+/// byte buffer for that strip.
+pub fn verify_strip<const N_BYTES: usize>(
+    report: &WaveformReport<N_BYTES>,
+    logical_strip: &LogicalStrip,
+    strip_index: usize,
+) -> Result<(), ByteMismatch> {
+    let expected = logical_strip.strip_bytes(strip_index);
+    let length = expected.len().max(report.bytes.len());
+    for index in 0..length {
+        let (expected, decoded) = (expected.get(index).copied(), report.bytes.get(index).copied());
+        if expected != decoded {
+            return Err(ByteMismatch { index, expected, decoded });
+        }
+    }
+    Ok(())
+}
+
+/// Maps the bytes sent to a strip back to the color each LED received, in the same order the
+/// `LogicalStrip` indexes them. This undoes the strip's `ColorOrder` and `reversed` setting but
+/// not gamma correction. For RGBW strips only the color channels are returned, not the white.
+pub fn received_colors<'b>(
+    strip: &'b PhysicalStrip,
+    bytes: &'b [u8],
+) -> impl Iterator<Item = Color> + 'b {
+    let [r, g, b] = strip.color_order.offsets();
+    let bytes_per_led = strip.pixel_format.bytes_per_led();
+    (0..strip.led_count).map(move |index| {
+        let wire_index = match strip.reversed {
+            true => strip.led_count - 1 - index,
+            false => index,
+        };
+        let led = &bytes[wire_index * bytes_per_led..];
+        Color::new(led[r], led[g], led[b])
+    })
+}
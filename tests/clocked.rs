@@ -0,0 +1,199 @@
+//! Frames bytes for the clocked protocols and sends a logical strip that mixes them with a
+//! WS28xx strip through the SPI and bit-bang backends.
+
+use bl602_ws2811::*;
+
+use colors::{self as c, Color, Color16};
+use hardware::{DynamicPin, HardwareController};
+use leds::{apa102, lpd8806, ws2801, ws28xx as strip};
+use simulator::VirtualClock;
+use spi::{SpiController, SpiEncoding, SpiOutput};
+use strip::{FrameError, Protocol};
+use waveform::{decode, verify_strip, RecordingPin};
+
+const fn bgr_strip(led_count: usize, protocol: Protocol) -> strip::PhysicalStrip {
+    strip::PhysicalStrip {
+        gamma: c::GammaCurve::LINEAR,
+        protocol,
+        ..strip::PhysicalStrip::new(
+            led_count,
+            strip::ColorOrder::BGR,
+            strip::StripTimings::WS2812_ADAFRUIT,
+        )
+    }
+}
+
+const APA102: Protocol = Protocol::Apa102 {
+    global_brightness: 16,
+    clock_hz: 8_000_000,
+};
+const LPD8806: Protocol = Protocol::Lpd8806 {
+    clock_hz: 2_000_000,
+};
+const STRIPS: [strip::PhysicalStrip; 3] = [
+    bgr_strip(2, Protocol::Ws28xx),
+    bgr_strip(2, APA102),
+    bgr_strip(1, LPD8806),
+];
+const NUM_LEDS: usize = get_total_num_leds(&STRIPS);
+const NUM_BYTES: usize = get_total_num_bytes(&STRIPS);
+
+#[test]
+fn apa102_frames_each_led_with_its_brightness() {
+    let mut out = [0; 17];
+    assert_eq!(apa102::encoded_len(6), 17);
+    assert_eq!(apa102::encode(&[1, 2, 3, 4, 5, 6], 31, &mut out), Ok(17));
+    assert_eq!(
+        out,
+        [0, 0, 0, 0, 0xFF, 1, 2, 3, 0xFF, 4, 5, 6, 0, 0, 0, 0, 0]
+    );
+
+    // only the low 5 bits of the brightness fit:
+    apa102::encode(&[1, 2, 3, 4, 5, 6], 0x25, &mut out).unwrap();
+    assert_eq!([out[4], out[8]], [0xE5, 0xE5]);
+
+    // the end frame grows by a byte for every 16 LEDs:
+    assert_eq!(apa102::encoded_len(3 * 16), 4 + 16 * 4 + 5);
+    assert_eq!(apa102::encoded_len(3 * 17), 4 + 17 * 4 + 6);
+
+    assert_eq!(
+        apa102::encode(&[1, 2], 31, &mut out),
+        Err(FrameError::PartialLed)
+    );
+    let error = apa102::encode(&[0; 6], 31, &mut [0; 16]);
+    assert_eq!(error, Err(FrameError::TooManyBytes { capacity: 16 }));
+}
+
+#[test]
+fn ws2801_sends_the_bytes_as_they_are() {
+    let mut out = [0; 6];
+    assert_eq!(ws2801::encode(&[1, 2, 3, 4, 5, 6], &mut out), Ok(6));
+    assert_eq!(out, [1, 2, 3, 4, 5, 6]);
+    assert_eq!(
+        ws2801::encode(&[1, 2, 3, 4], &mut out),
+        Err(FrameError::PartialLed)
+    );
+}
+
+#[test]
+fn lpd8806_sends_seven_bits_and_latches() {
+    let mut out = [0; 7];
+    assert_eq!(lpd8806::encode(&[255, 0, 128, 1, 2, 3], &mut out), Ok(7));
+    assert_eq!(out, [0xFF, 0x80, 0xC0, 0x80, 0x81, 0x81, 0]);
+
+    // a latch byte for every 32 LEDs:
+    assert_eq!(lpd8806::encoded_len(3 * 32), 3 * 32 + 1);
+    assert_eq!(lpd8806::encoded_len(3 * 33), 3 * 33 + 2);
+}
+
+// keeps a copy of everything written:
+#[derive(Default)]
+struct CaptureOutput {
+    writes: Vec<(usize, u32, Vec<u8>)>,
+}
+
+impl SpiOutput for &mut CaptureOutput {
+    fn write(&mut self, strip_index: usize, clock_hz: u32, data: &[u8]) {
+        self.writes.push((strip_index, clock_hz, data.to_vec()));
+    }
+}
+
+#[test]
+fn one_logical_strip_mixes_protocols() {
+    let mut byte_buffer = [0; NUM_BYTES];
+    let mut color_buffer = [Color16::default(); NUM_LEDS];
+    let mut strips = STRIPS;
+    let mut logical_strip =
+        strip::LogicalStrip::new(&mut byte_buffer, &mut color_buffer, &mut strips);
+    let colors = [
+        c::C_RED,
+        c::C_GREEN,
+        c::C_BLUE,
+        Color::new(10, 20, 30),
+        c::C_WHITE,
+    ];
+    for (index, color) in colors.iter().enumerate() {
+        logical_strip.set_color_at_index(index, *color);
+    }
+
+    let ws2812 = SpiEncoding::new(&strip::StripTimings::WS2812_ADAFRUIT, 3).unwrap();
+    let encodings = [ws2812; 3];
+    let mut output = CaptureOutput::default();
+    let mut buffer = [0; 84 + 6 * 3];
+    let strips = logical_strip.strips();
+    let mut driver = SpiController::new(&mut output, strips, &encodings, &mut buffer).unwrap();
+    logical_strip.send_all_sequential(&mut driver);
+
+    let [(_, ws_clock, ws_data), (_, apa_clock, apa_data), (_, lpd_clock, lpd_data)] =
+        &output.writes[..]
+    else {
+        panic!("expected a write for each strip");
+    };
+
+    // the WS2812 strip is sent as symbols:
+    assert_eq!(*ws_clock, 2_400_000);
+    let mut decoded = [0; 6];
+    assert_eq!(spi::decode(&ws2812, ws_data, &mut decoded), Ok(6));
+    assert_eq!(decoded, logical_strip.strip_bytes(0));
+
+    // the clocked strips at their own clocks, in BGR after the brightness:
+    assert_eq!(*apa_clock, 8_000_000);
+    assert_eq!(
+        apa_data[..12],
+        [0, 0, 0, 0, 0xF0, 255, 0, 0, 0xF0, 30, 20, 10]
+    );
+    assert_eq!(apa_data.len(), apa102::encoded_len(6));
+    assert_eq!(*lpd_clock, 2_000_000);
+    assert_eq!(lpd_data[..], [0xFF, 0xFF, 0xFF, 0]);
+
+    let mut out = [0; 32];
+    assert_eq!(logical_strip.encode_strip(2, &mut out), Ok(4));
+    assert_eq!(out[..4], lpd_data[..]);
+}
+
+#[test]
+fn clocked_strips_only_take_rgb() {
+    let mut strips = [bgr_strip(3, APA102), bgr_strip(1, LPD8806)];
+    strips[0].pixel_format = strip::PixelFormat::Rgbw(c::WhiteExtraction::Min);
+    let mut byte_buffer = [0; 15];
+    let mut color_buffer = [Color16::default(); 4];
+    let logical_strip = strip::LogicalStrip::new(&mut byte_buffer, &mut color_buffer, &mut strips);
+    let error = logical_strip.encode_strip(0, &mut [0; 32]);
+    assert_eq!(error, Err(FrameError::UnsupportedPixelFormat));
+
+    // the SPI backend skips the RGBW strip rather than framing its 12 bytes as 4 LEDs:
+    let encodings = [SpiEncoding::new(&strip::StripTimings::WS2812_ADAFRUIT, 3).unwrap(); 2];
+    let mut output = CaptureOutput::default();
+    let mut buffer = [0; 32];
+    let strips = logical_strip.strips();
+    let mut driver = SpiController::new(&mut output, strips, &encodings, &mut buffer).unwrap();
+    logical_strip.send_all_sequential(&mut driver);
+    let strip_indexes: Vec<usize> = output.writes.iter().map(|write| write.0).collect();
+    assert_eq!(strip_indexes, [1]);
+}
+
+#[test]
+fn bit_banging_skips_clocked_strips() {
+    let mut byte_buffer = [0; NUM_BYTES];
+    let mut color_buffer = [Color16::default(); NUM_LEDS];
+    let mut strips = STRIPS;
+    let mut logical_strip =
+        strip::LogicalStrip::new(&mut byte_buffer, &mut color_buffer, &mut strips);
+    for index in 0..NUM_LEDS {
+        logical_strip.set_color_at_index(index, c::C_WHITE);
+    }
+
+    let clock = VirtualClock::new();
+    let mut pins = [(); 3].map(|_| RecordingPin::<{ NUM_BYTES * 16 + 1 }>::new(&clock, 0));
+    {
+        let [pin_0, pin_1, pin_2] = &mut pins;
+        let mut pins: [DynamicPin; 3] = [pin_0, pin_1, pin_2];
+        let mut hc = HardwareController::new(&mut pins, clock.free_running_timer());
+        logical_strip.send_all_sequential(&mut hc);
+    }
+
+    let report = decode::<NUM_BYTES>(pins[0].edges(), &STRIPS[0].strip_timings);
+    assert!(report.is_in_spec(), "{:?}", report.violations);
+    assert_eq!(verify_strip(&report, &logical_strip, 0), Ok(()));
+    assert!(pins[1].edges().is_empty() && pins[2].edges().is_empty());
+}
@@ -0,0 +1,326 @@
+//! Checks the HSV and HSL conversions against known colors and each other, the const palette
+//! generators built on them, and fades through each `Interpolation`.
+
+use bl602_ws2811::colors::{self as c, Color, Color16, Hsl, Hsv, Interpolation};
+
+fn rgb(color: Color) -> [u8; 3] {
+    [color.r, color.g, color.b]
+}
+
+// the largest difference between any channel of the two colors:
+fn distance(a: Color, b: Color) -> u8 {
+    let [a, b] = [rgb(a), rgb(b)];
+    (0..3).map(|i| a[i].abs_diff(b[i])).max().unwrap()
+}
+
+// every color on a grid through the RGB cube, corners included:
+fn color_grid() -> impl Iterator<Item = Color> {
+    let steps = || (0..=255).step_by(5);
+    steps().flat_map(move |r| steps().flat_map(move |g| steps().map(move |b| Color::new(r, g, b))))
+}
+
+#[test]
+fn hsv_primaries_and_greys() {
+    let hues = [0, 60, 120, 180, 240, 300];
+    let expected = [
+        [255, 0, 0],
+        [255, 255, 0],
+        [0, 255, 0],
+        [0, 255, 255],
+        [0, 0, 255],
+        [255, 0, 255],
+    ];
+    for (hue, expected) in hues.iter().zip(expected) {
+        assert_eq!(
+            rgb(Hsv::new(*hue, 255, 255).to_color()),
+            expected,
+            "hue {}",
+            hue
+        );
+        assert_eq!(Hsv::from_color(expected.into()), Hsv::new(*hue, 255, 255));
+    }
+
+    // with no saturation the hue makes no difference, and greys come back with a hue of 0:
+    for hue in [0, 90, 200, 359] {
+        assert_eq!(rgb(Hsv::new(hue, 0, 128).to_color()), [128; 3]);
+    }
+    assert_eq!(
+        Hsv::from_color(Color::new(128, 128, 128)),
+        Hsv::new(0, 0, 128)
+    );
+    assert_eq!(Hsv::from_color(c::C_OFF), Hsv::new(0, 0, 0));
+    assert_eq!(Hsv::from_color(c::C_WHITE), Hsv::new(0, 0, 255));
+    assert_eq!(rgb(Hsv::new(120, 255, 0).to_color()), [0; 3]);
+}
+
+#[test]
+fn hsl_primaries_and_greys() {
+    // a fully saturated hue is half way up the lightness, which rounds up to 128:
+    assert_eq!(Hsl::from_color(c::C_RED), Hsl::new(0, 255, 128));
+    assert_eq!(Hsl::from_color(c::C_GREEN), Hsl::new(120, 255, 128));
+    assert_eq!(Hsl::from_color(c::C_BLUE), Hsl::new(240, 255, 128));
+    assert_eq!(
+        Hsl::from_color([255, 255, 0].into()),
+        Hsl::new(60, 255, 128)
+    );
+    for (hue, expected) in [(0, c::C_RED), (120, c::C_GREEN), (240, c::C_BLUE)] {
+        let color = Hsl::new(hue, 255, 128).to_color();
+        assert!(distance(color, expected) <= 1, "hue {}: {:?}", hue, color);
+    }
+
+    for hue in [0, 90, 200, 359] {
+        assert_eq!(rgb(Hsl::new(hue, 0, 100).to_color()), [100; 3]);
+        assert_eq!(rgb(Hsl::new(hue, 255, 255).to_color()), [255; 3]);
+        assert_eq!(rgb(Hsl::new(hue, 255, 0).to_color()), [0; 3]);
+    }
+    assert_eq!(
+        Hsl::from_color(Color::new(100, 100, 100)),
+        Hsl::new(0, 0, 100)
+    );
+    assert_eq!(Hsl::from_color(c::C_WHITE), Hsl::new(0, 0, 255));
+}
+
+#[test]
+fn hues_wrap_around_at_360() {
+    for (hue, wrapped) in [(360, 0), (480, 120), (719, 359)] {
+        assert_eq!(
+            rgb(Hsv::new(hue, 255, 255).to_color()),
+            rgb(Hsv::new(wrapped, 255, 255).to_color())
+        );
+        assert_eq!(
+            rgb(Hsl::new(hue, 200, 100).to_color()),
+            rgb(Hsl::new(wrapped, 200, 100).to_color())
+        );
+    }
+    assert_eq!(Hsv::new(350, 255, 255).rotate(20), Hsv::new(10, 255, 255));
+    assert_eq!(Hsv::new(400, 255, 255).rotate(400), Hsv::new(80, 255, 255));
+    assert_eq!(Hsl::new(300, 255, 128).rotate(90), Hsl::new(30, 255, 128));
+}
+
+#[test]
+fn colors_survive_a_round_trip() {
+    // hue, saturation and value are rounded to whole steps, which costs a few levels at most:
+    for color in color_grid() {
+        let hsv = Hsv::from_color(color).to_color();
+        assert!(
+            distance(hsv, color) <= 2,
+            "{:?} came back from HSV as {:?}",
+            color,
+            hsv
+        );
+        let hsl = Hsl::from_color(color).to_color();
+        assert!(
+            distance(hsl, color) <= 3,
+            "{:?} came back from HSL as {:?}",
+            color,
+            hsl
+        );
+    }
+
+    // and every hue of a fully saturated color comes back exactly:
+    for hue in 0..360 {
+        assert_eq!(Hsv::from_color(Hsv::new(hue, 255, 255).to_color()).h, hue);
+        assert_eq!(Hsl::from_color(Hsl::new(hue, 255, 128).to_color()).h, hue);
+    }
+}
+
+#[test]
+fn hue_wheels_are_spaced_evenly() {
+    const WHEEL: [Color; 6] = c::hue_wheel(0, 255, 255);
+    let expected = [
+        [255, 0, 0],
+        [255, 255, 0],
+        [0, 255, 0],
+        [0, 255, 255],
+        [0, 0, 255],
+        [255, 0, 255],
+    ];
+    assert_eq!(WHEEL.map(rgb), expected);
+
+    // starting past red wraps back around to it:
+    const FROM_MAGENTA: [Color; 4] = c::hue_wheel(300, 255, 255);
+    let expected = [[255, 0, 255], [255, 128, 0], [0, 255, 0], [0, 128, 255]];
+    assert_eq!(FROM_MAGENTA.map(rgb), expected);
+
+    // without saturation every color on the wheel is the same grey:
+    const GREYS: [Color; 5] = c::hue_wheel(45, 0, 90);
+    assert_eq!(GREYS.map(rgb), [[90; 3]; 5]);
+}
+
+#[test]
+fn hue_schemes_and_ramps() {
+    const RED: Hsv = Hsv::new(0, 255, 255);
+    const COMPLEMENTARY: [Color; 2] = c::complementary(RED);
+    const TRIADIC: [Color; 3] = c::triadic(Hsv::new(60, 255, 255));
+    const ANALOGOUS: [Color; 3] = c::analogous(RED, 30);
+    assert_eq!(COMPLEMENTARY.map(rgb), [[255, 0, 0], [0, 255, 255]]);
+    assert_eq!(
+        TRIADIC.map(rgb),
+        [[255, 255, 0], [0, 255, 255], [255, 0, 255]]
+    );
+    // the neighbour below red wraps around to 330:
+    assert_eq!(
+        ANALOGOUS.map(rgb),
+        [[255, 0, 128], [255, 0, 0], [255, 128, 0]]
+    );
+
+    const SATURATION: [Color; 3] = c::saturation_ramp(0, 255, 0, 255);
+    const VALUE: [Color; 3] = c::value_ramp(240, 255, 0, 255);
+    assert_eq!(
+        SATURATION.map(rgb),
+        [[255, 255, 255], [255, 128, 128], [255, 0, 0]]
+    );
+    assert_eq!(VALUE.map(rgb), [[0, 0, 0], [0, 0, 127], [0, 0, 255]]);
+}
+
+const CURVED: [Interpolation; 4] = [
+    Interpolation::HsvShortest,
+    Interpolation::HsvLongest,
+    Interpolation::Oklab,
+    Interpolation::Oklch,
+];
+
+// the color `percent` of the way from start to end:
+fn lerp(interpolation: Interpolation, percent: i32, start: Color, end: Color) -> [u8; 3] {
+    rgb(Color::color_lerp_in(
+        interpolation,
+        percent,
+        0,
+        100,
+        start,
+        end,
+    ))
+}
+
+#[test]
+fn interpolation_starts_and_ends_on_its_colors() {
+    let pairs = [
+        (c::C_RED, c::C_BLUE),
+        (Color::new(200, 100, 50), Color::new(10, 20, 30)),
+        (c::C_OFF, c::C_WHITE),
+        (c::C_WHITE, c::C_GREEN),
+    ];
+    for interpolation in CURVED {
+        for (start, end) in pairs {
+            assert_eq!(
+                lerp(interpolation, 0, start, end),
+                rgb(start),
+                "{:?}",
+                interpolation
+            );
+            assert_eq!(
+                lerp(interpolation, 100, start, end),
+                rgb(end),
+                "{:?}",
+                interpolation
+            );
+
+            // and so does the 16 bit version the curved fades are worked out in:
+            let (start16, end16) = (start.into(), end.into());
+            let wide = Color16::color_lerp_in(interpolation, 100, 0, 100, start16, end16);
+            assert_eq!(wide, end16, "{:?}", interpolation);
+        }
+    }
+
+    // an empty range stays on the start color rather than dividing by zero:
+    for interpolation in CURVED {
+        let color = Color::color_lerp_in(interpolation, 5, 5, 5, c::C_RED, c::C_BLUE);
+        assert_eq!(rgb(color), [255, 0, 0]);
+    }
+}
+
+#[test]
+fn wide_fades_keep_their_low_bits() {
+    // colors between the steps of a Color, which rounding them to one would move:
+    let (start, end) = (
+        Color16::new(1000, 20_000, 50_000),
+        Color16::new(60_000, 30_100, 129),
+    );
+    for interpolation in CURVED {
+        let fade = |percent| Color16::color_lerp_in(interpolation, percent, 0, 100, start, end);
+        assert_eq!(fade(0), start, "{:?}", interpolation);
+        assert_eq!(fade(100), end, "{:?}", interpolation);
+    }
+
+    // and the middle of a fade isn't rounded to the nearest Color either:
+    let grey = Color16::color_lerp_in(
+        Interpolation::Oklab,
+        50,
+        0,
+        100,
+        c::C_OFF.into(),
+        c::C_WHITE.into(),
+    );
+    assert_eq!(rgb(grey.to_color()), [99; 3]);
+    assert_ne!(grey, Color16::from(grey.to_color()));
+}
+
+#[test]
+fn hsv_takes_the_way_around_it_was_asked_for() {
+    let (magenta, orange) = (
+        Hsv::new(340, 255, 255).to_color(),
+        Hsv::new(20, 255, 255).to_color(),
+    );
+
+    // red to blue is shorter going backwards through magenta than forwards through green:
+    assert_eq!(
+        lerp(Interpolation::HsvShortest, 50, c::C_RED, c::C_BLUE),
+        [255, 0, 255]
+    );
+    assert_eq!(
+        lerp(Interpolation::HsvLongest, 50, c::C_RED, c::C_BLUE),
+        [0, 255, 0]
+    );
+    // and 340 to 20 is shorter across 0 than back through 180:
+    assert_eq!(
+        lerp(Interpolation::HsvShortest, 50, magenta, orange),
+        [255, 0, 0]
+    );
+    assert_eq!(
+        lerp(Interpolation::HsvLongest, 50, magenta, orange),
+        [0, 255, 255]
+    );
+
+    // greys take the hue of the other end instead of sweeping the wheel from red:
+    for interpolation in [Interpolation::HsvShortest, Interpolation::HsvLongest] {
+        assert_eq!(
+            lerp(interpolation, 50, c::C_WHITE, c::C_BLUE),
+            [128, 128, 255]
+        );
+        assert_eq!(lerp(interpolation, 50, c::C_OFF, c::C_WHITE), [128; 3]);
+    }
+}
+
+#[test]
+fn oklab_fades_stay_between_their_colors() {
+    // a straight line from red to blue only mixes the two, adding no green:
+    let [r, g, b] = lerp(Interpolation::Oklab, 50, c::C_RED, c::C_BLUE);
+    assert!(g < r && g < b, "{:?}", [r, g, b]);
+    // greys stay grey, at the perceived half way point rather than half the channel value:
+    assert_eq!(
+        lerp(Interpolation::Oklab, 50, c::C_OFF, c::C_WHITE),
+        [99; 3]
+    );
+}
+
+#[test]
+fn oklch_takes_the_short_way_around() {
+    // red to blue goes through purple rather than through green:
+    let [r, g, b] = lerp(Interpolation::Oklch, 50, c::C_RED, c::C_BLUE);
+    assert!(g == 0 && r > 128 && b > 128, "{:?}", [r, g, b]);
+    // and 340 to 20 crosses red rather than going back through cyan:
+    let (magenta, orange) = (
+        Hsv::new(340, 255, 255).to_color(),
+        Hsv::new(20, 255, 255).to_color(),
+    );
+    let [r, g, b] = lerp(Interpolation::Oklch, 50, magenta, orange);
+    assert!(r == 255 && g < 64 && b < 64, "{:?}", [r, g, b]);
+
+    // a grey end takes the hue of the other end, so it doesn't add one of its own:
+    assert_eq!(
+        lerp(Interpolation::Oklch, 50, c::C_OFF, c::C_WHITE),
+        [99; 3]
+    );
+    let [r, g, b] = lerp(Interpolation::Oklch, 50, c::C_OFF, c::C_RED);
+    assert!(g == b && r > g, "{:?}", [r, g, b]);
+}
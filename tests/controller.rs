@@ -0,0 +1,124 @@
+//! Runs a `LightingController` in the simulator and checks what it does to the bytes sent to
+//! the LEDs, for the settings that apply on top of the animations.
+
+use bl602_ws2811::*;
+
+use animations::{Animatable, Animation, AnimationParameters};
+use colors::{self as c, Color16};
+use default_animations as da;
+use embedded_time::rate::*;
+use leds::ws28xx as strip;
+use lighting_controller::LightingController;
+use simulator::{Simulator, VirtualClock};
+use transition::Transition;
+
+const NUM_LEDS: usize = 4;
+const FRAME_RATE_HZ: u32 = 60;
+
+const WHITE: AnimationParameters = AnimationParameters {
+    bg: background::Parameters {
+        mode: background::Mode::Solid,
+        rainbow: c::Palette::Rainbow(c::R_ON),
+        ..da::BG_OFF
+    },
+    fg: da::FG_OFF,
+    trigger: da::TRIGGER_OFF,
+};
+
+const LINEAR_STRIP: [strip::PhysicalStrip; 1] = [strip::PhysicalStrip {
+    gamma: c::GammaCurve::LINEAR,
+    ..strip::PhysicalStrip::new(
+        NUM_LEDS,
+        strip::ColorOrder::RGB,
+        strip::StripTimings::WS2812_ADAFRUIT,
+    )
+}];
+
+#[test]
+fn brightness_fades_every_frame_without_touching_the_animation() {
+    let clock = VirtualClock::new();
+    let mut timer = clock.timer();
+    let mut byte_buffer = [0; NUM_LEDS * 3];
+    let mut color_buffer = [Color16::default(); NUM_LEDS];
+    let mut strips = LINEAR_STRIP;
+    let logical_strip = strip::LogicalStrip::new(&mut byte_buffer, &mut color_buffer, &mut strips);
+
+    let translation_array = utility::default_translation_array::<NUM_LEDS>(0);
+    let mut animation = Animation::new(WHITE, translation_array, FRAME_RATE_HZ.Hz());
+    let animations: [&mut dyn Animatable; 1] = [&mut animation];
+
+    let lc = LightingController::new(logical_strip, animations, FRAME_RATE_HZ.Hz(), &mut timer);
+    let mut sim: Simulator<1, { NUM_LEDS * 3 }> = Simulator::new(&clock, lc);
+
+    sim.step();
+    assert_eq!(sim.bytes(), [255; NUM_LEDS * 3]);
+
+    // six frames at 60Hz, each a step darker than the last:
+    sim.controller().fade_to(0, 100_000_000);
+    let mut last = 255;
+    for _ in 0..6 {
+        sim.step();
+        assert!(sim.bytes()[0] < last);
+        last = sim.bytes()[0];
+        // the animation itself is still white:
+        assert_eq!(sim.colors()[0], Color16::from(c::C_WHITE));
+    }
+    assert_eq!(sim.bytes(), [0; NUM_LEDS * 3]);
+    assert_eq!(sim.controller().brightness(), 0);
+
+    sim.controller().set_brightness(128);
+    sim.step();
+    assert_eq!(sim.bytes(), [128; NUM_LEDS * 3]);
+    assert_eq!(sim.controller().brightness(), 128);
+}
+
+#[test]
+fn transitions_match_leds_by_their_physical_index() {
+    let clock = VirtualClock::new();
+    let mut timer = clock.timer();
+    let mut byte_buffer = [0; NUM_LEDS * 3];
+    let mut color_buffer = [Color16::default(); NUM_LEDS];
+    let mut strips = LINEAR_STRIP;
+    let logical_strip = strip::LogicalStrip::new(&mut byte_buffer, &mut color_buffer, &mut strips);
+
+    // the same LEDs in the opposite order:
+    let mut outgoing = Animation::new(WHITE, [0, 1, 2, 3], FRAME_RATE_HZ.Hz());
+    let mut incoming = Animation::new(WHITE, [3, 2, 1, 0], FRAME_RATE_HZ.Hz());
+    let animations: [&mut dyn Animatable; 1] = [&mut outgoing];
+
+    let lc = LightingController::new(logical_strip, animations, FRAME_RATE_HZ.Hz(), &mut timer);
+    let mut sim: Simulator<1, { NUM_LEDS * 3 }> = Simulator::new(&clock, lc);
+    sim.step();
+
+    // white to white stays white on every frame, rather than dipping towards off:
+    sim.controller()
+        .replace_animation_with(0, &mut incoming, Transition::Crossfade, 100_000_000);
+    while sim.controller().is_transitioning(0) {
+        sim.step();
+        assert_eq!(sim.bytes(), [255; NUM_LEDS * 3]);
+    }
+}
+
+#[test]
+fn animations_too_long_to_transition_are_cut_over() {
+    let clock = VirtualClock::new();
+    let mut timer = clock.timer();
+    let mut byte_buffer = [0; NUM_LEDS * 3];
+    let mut color_buffer = [Color16::default(); NUM_LEDS];
+    let mut strips = LINEAR_STRIP;
+    let logical_strip = strip::LogicalStrip::new(&mut byte_buffer, &mut color_buffer, &mut strips);
+
+    // more positions than a transition has room to match up, all on the first LED:
+    const LONG: usize = transition::MAX_NUM_TRANSITION_LEDS + 1;
+    let mut outgoing = Animation::new(WHITE, [0, 1, 2, 3], FRAME_RATE_HZ.Hz());
+    let mut incoming = Animation::new(WHITE, [0; LONG], FRAME_RATE_HZ.Hz());
+    let animations: [&mut dyn Animatable; 1] = [&mut outgoing];
+
+    let lc = LightingController::new(logical_strip, animations, FRAME_RATE_HZ.Hz(), &mut timer);
+    let mut sim: Simulator<1, { NUM_LEDS * 3 }> = Simulator::new(&clock, lc);
+    sim.step();
+
+    sim.controller()
+        .replace_animation_with(0, &mut incoming, Transition::Crossfade, 100_000_000);
+    assert!(!sim.controller().is_transitioning(0));
+}
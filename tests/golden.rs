@@ -0,0 +1,598 @@
+//! Golden-frame regression tests for the built-in animation modes.
+//!
+//! Each test renders an `AnimationParameters` for a fixed number of frames in the simulator,
+//! firing a scripted list of triggers along the way, and compares every frame against the file
+//! of the same name in `tests/golden/`. To regenerate the files after an intentional change to
+//! the look of an animation, run the tests with `UPDATE_GOLDEN=1` set and review the diff.
+
+use bl602_ws2811::*;
+
+use animations::{
+    Animatable, Animation, AnimationParameters, Direction, LayerParameters, Mask, StackError,
+};
+use colors::{self as c, Color16};
+use default_animations as da;
+use embedded_time::rate::*;
+use leds::ws28xx as strip;
+use lighting_controller::LightingController;
+use simulator::{Simulator, VirtualClock, VirtualTimer};
+use std::fmt::Write;
+use transition::Transition;
+
+const NUM_LEDS: usize = 16;
+const NUM_FRAMES: usize = 32;
+const FRAME_RATE_HZ: u32 = 60;
+const SEED: u64 = 0x1234;
+
+const GOLDEN_STRIP: [strip::PhysicalStrip; 1] = [strip::PhysicalStrip::new(
+    NUM_LEDS,
+    strip::ColorOrder::RGB,
+    strip::StripTimings::WS2812_ADAFRUIT,
+)];
+
+/// Renders `parameters` for `NUM_FRAMES` frames. Each `(frame, trigger)` pair in `triggers` is
+/// fired just before that frame is rendered.
+fn render(parameters: AnimationParameters, triggers: &[(usize, trigger::Parameters)]) -> String {
+    render_stack(parameters.into(), |lc, frame| {
+        for (_, params) in triggers.iter().filter(|(at, _)| *at == frame) {
+            lc.trigger(0, params);
+        }
+    })
+}
+
+/// Renders a stack of layers for `NUM_FRAMES` frames, calling `before_frame` with the controller
+/// and the frame number just before each frame is rendered.
+fn render_stack<const N_LAYER: usize>(
+    layers: [LayerParameters; N_LAYER],
+    before_frame: impl Fn(&mut LightingController<VirtualTimer, 1>, usize),
+) -> String {
+    let clock = VirtualClock::new();
+    let mut timer = clock.timer();
+    let mut byte_buffer = [0; NUM_LEDS * 3];
+    let mut color_buffer = [Color16::default(); NUM_LEDS];
+    let mut strips = GOLDEN_STRIP;
+    let logical_strip = strip::LogicalStrip::new(&mut byte_buffer, &mut color_buffer, &mut strips);
+
+    let translation_array = utility::default_translation_array::<NUM_LEDS>(0);
+    let mut animation =
+        Animation::new_stack_with_seed(layers, translation_array, FRAME_RATE_HZ.Hz(), SEED)
+            .unwrap();
+    let animations: [&mut dyn Animatable; 1] = [&mut animation];
+
+    let lc = LightingController::new(logical_strip, animations, FRAME_RATE_HZ.Hz(), &mut timer);
+    let mut sim: Simulator<1, { NUM_LEDS * 3 }> = Simulator::new(&clock, lc);
+
+    let mut rendered = String::new();
+    for frame in 0..NUM_FRAMES {
+        before_frame(sim.controller(), frame);
+        let line: Vec<String> = sim
+            .step()
+            .iter()
+            .map(|c| c.to_color())
+            .map(|c| format!("{:02x}{:02x}{:02x}", c.r, c.g, c.b))
+            .collect();
+        writeln!(rendered, "{}", line.join(" ")).unwrap();
+    }
+    rendered
+}
+
+/// Renders a rainbow for `NUM_FRAMES` frames, replacing it with a solid white animation using
+/// `transition` at frame 4. The transition lasts 21 frames.
+fn render_transition(transition: Transition) -> String {
+    let clock = VirtualClock::new();
+    let mut timer = clock.timer();
+    let mut byte_buffer = [0; NUM_LEDS * 3];
+    let mut color_buffer = [Color16::default(); NUM_LEDS];
+    let mut strips = GOLDEN_STRIP;
+    let logical_strip = strip::LogicalStrip::new(&mut byte_buffer, &mut color_buffer, &mut strips);
+
+    let translation_array = utility::default_translation_array::<NUM_LEDS>(0);
+    let rainbow = background_only(background::Mode::FillRainbow, c::R_ROYGBIV);
+    let white = background_only(background::Mode::Solid, c::R_ON);
+    let mut outgoing =
+        Animation::new_with_seed(rainbow, translation_array, FRAME_RATE_HZ.Hz(), SEED);
+    let mut incoming = Animation::new_with_seed(white, translation_array, FRAME_RATE_HZ.Hz(), SEED);
+    let animations: [&mut dyn Animatable; 1] = [&mut outgoing];
+
+    let lc = LightingController::new(logical_strip, animations, FRAME_RATE_HZ.Hz(), &mut timer);
+    let mut sim: Simulator<1, { NUM_LEDS * 3 }> = Simulator::new(&clock, lc);
+
+    let mut rendered = String::new();
+    let mut incoming = Some(&mut incoming);
+    for frame in 0..NUM_FRAMES {
+        if frame == 4 {
+            let incoming = incoming.take().unwrap();
+            sim.controller()
+                .replace_animation_with(0, incoming, transition, 350_000_000);
+        }
+        let line: Vec<String> = sim
+            .step()
+            .iter()
+            .map(|c| c.to_color())
+            .map(|c| format!("{:02x}{:02x}{:02x}", c.r, c.g, c.b))
+            .collect();
+        writeln!(rendered, "{}", line.join(" ")).unwrap();
+    }
+    assert!(
+        !sim.controller().is_transitioning(0),
+        "the transition should have finished"
+    );
+    rendered
+}
+
+fn check_golden(
+    name: &str,
+    parameters: AnimationParameters,
+    triggers: &[(usize, trigger::Parameters)],
+) {
+    compare_golden(name, &render(parameters, triggers));
+}
+
+fn compare_golden(name: &str, rendered: &str) {
+    let path = format!("{}/tests/golden/{}.txt", env!("CARGO_MANIFEST_DIR"), name);
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, rendered).unwrap();
+        return;
+    }
+
+    let golden = std::fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("missing {}, run with UPDATE_GOLDEN=1 to create it", path));
+
+    for (frame, (expected, actual)) in golden.lines().zip(rendered.lines()).enumerate() {
+        let leds = expected.split(' ').zip(actual.split(' ')).enumerate();
+        for (led, (expected, actual)) in leds {
+            assert_eq!(
+                expected, actual,
+                "{}: frame {} led {} differs",
+                name, frame, led
+            );
+        }
+    }
+    assert_eq!(
+        golden.lines().count(),
+        rendered.lines().count(),
+        "{}: frame count differs",
+        name
+    );
+}
+
+fn bg(
+    mode: background::Mode,
+    rainbow: impl Into<c::Palette<'static>>,
+) -> background::Parameters<'static> {
+    background::Parameters {
+        mode,
+        rainbow: rainbow.into(),
+        direction: Direction::Positive,
+        is_rainbow_forward: true,
+        duration_ns: 400_000_000,
+        subdivisions: 1,
+        interpolation: c::Interpolation::LinearRgb,
+        blend: c::LayerBlend::OPAQUE,
+        mask: Mask::None,
+    }
+}
+
+fn fg(
+    mode: foreground::Mode,
+    rainbow: impl Into<c::Palette<'static>>,
+) -> foreground::Parameters<'static> {
+    foreground::Parameters {
+        mode,
+        rainbow: rainbow.into(),
+        direction: Direction::Positive,
+        is_rainbow_forward: true,
+        duration_ns: 300_000_000,
+        step_time_ns: 100_000_000,
+        subdivisions: 1,
+        pixels_per_pixel_group: 2,
+        interpolation: c::Interpolation::LinearRgb,
+        blend: c::LayerBlend::OPAQUE,
+        mask: Mask::None,
+    }
+}
+
+const TRIGGER_GLOBAL: trigger::GlobalParameters = trigger::GlobalParameters {
+    rainbow: c::Palette::Rainbow(c::R_ROYGBIV),
+    is_rainbow_forward: true,
+    duration_ns: 200_000_000,
+    interpolation: c::Interpolation::LinearRgb,
+    blend: c::LayerBlend::OPAQUE,
+    mask: Mask::None,
+};
+
+fn trigger_params(mode: trigger::Mode) -> trigger::Parameters {
+    trigger::Parameters {
+        mode,
+        direction: Direction::Positive,
+        fade_in_time_ns: 100_000_000,
+        fade_out_time_ns: 200_000_000,
+        starting_offset: 0,
+        pixels_per_pixel_group: 2,
+    }
+}
+
+fn background_only(
+    mode: background::Mode,
+    rainbow: impl Into<c::Palette<'static>>,
+) -> AnimationParameters<'static> {
+    AnimationParameters {
+        bg: bg(mode, rainbow),
+        fg: da::FG_OFF,
+        trigger: da::TRIGGER_OFF,
+    }
+}
+
+fn foreground_only(mode: foreground::Mode) -> AnimationParameters<'static> {
+    AnimationParameters {
+        bg: da::BG_OFF,
+        fg: fg(mode, c::R_ROYGBIV),
+        trigger: da::TRIGGER_OFF,
+    }
+}
+
+/// Triggers are layered over a dim background so the frames show what they leave untouched.
+fn trigger_over_background(
+    mode: trigger::Mode,
+) -> (
+    AnimationParameters<'static>,
+    Vec<(usize, trigger::Parameters)>,
+) {
+    let parameters = AnimationParameters {
+        bg: bg(background::Mode::Solid, c::R_DARK_BLUE_PATTERN),
+        fg: da::FG_OFF,
+        trigger: TRIGGER_GLOBAL,
+    };
+    let triggers = vec![
+        (0, trigger_params(mode)),
+        (4, trigger_params(mode)),
+        (12, trigger_params(mode)),
+    ];
+    (parameters, triggers)
+}
+
+fn check_trigger(name: &str, mode: trigger::Mode) {
+    let (parameters, triggers) = trigger_over_background(mode);
+    check_golden(name, parameters, &triggers);
+}
+
+#[test]
+fn bg_no_background() {
+    check_golden(
+        "bg_no_background",
+        background_only(background::Mode::NoBackground, c::R_ROYGBIV),
+        &[],
+    );
+}
+
+#[test]
+fn bg_solid() {
+    let triggers = [(10, trigger_params(trigger::Mode::Background))];
+    check_golden(
+        "bg_solid",
+        background_only(background::Mode::Solid, c::R_ROYGBIV),
+        &triggers,
+    );
+}
+
+#[test]
+fn bg_solid_fade() {
+    let triggers = [(20, trigger_params(trigger::Mode::Background))];
+    check_golden(
+        "bg_solid_fade",
+        background_only(background::Mode::SolidFade, c::R_ROYGBIV),
+        &triggers,
+    );
+}
+
+#[test]
+fn bg_fill_rainbow() {
+    let triggers = [(16, trigger_params(trigger::Mode::Background))];
+    check_golden(
+        "bg_fill_rainbow",
+        background_only(background::Mode::FillRainbow, c::R_ROYGBIV),
+        &triggers,
+    );
+}
+
+#[test]
+fn bg_fill_rainbow_rotate() {
+    let triggers = [(16, trigger_params(trigger::Mode::Background))];
+    let parameters = background_only(background::Mode::FillRainbowRotate, c::R_ROYGBIV);
+    check_golden("bg_fill_rainbow_rotate", parameters, &triggers);
+}
+
+#[test]
+fn bg_fill_gradient() {
+    let parameters = background_only(background::Mode::FillRainbow, c::G_BLUE_GOLD_BAND);
+    check_golden("bg_fill_gradient", parameters, &[]);
+}
+
+#[test]
+fn bg_solid_fade_gradient() {
+    let triggers = [(2, trigger_params(trigger::Mode::Background))];
+    let parameters = background_only(background::Mode::SolidFade, c::G_BLUE_GOLD_BAND);
+    check_golden("bg_solid_fade_gradient", parameters, &triggers);
+}
+
+#[test]
+fn bg_temperature_drift() {
+    let mode = background::Mode::TemperatureDrift {
+        from_kelvin: 6500,
+        to_kelvin: 2200,
+    };
+    check_golden("bg_temperature_drift", background_only(mode, c::R_OFF), &[]);
+}
+
+#[test]
+fn fg_no_foreground() {
+    check_golden(
+        "fg_no_foreground",
+        foreground_only(foreground::Mode::NoForeground),
+        &[],
+    );
+}
+
+#[test]
+fn fg_marquee_solid() {
+    let triggers = [(16, trigger_params(trigger::Mode::Foreground))];
+    check_golden(
+        "fg_marquee_solid",
+        foreground_only(foreground::Mode::MarqueeSolid),
+        &triggers,
+    );
+}
+
+#[test]
+fn fg_marquee_solid_fixed() {
+    let triggers = [(16, trigger_params(trigger::Mode::Foreground))];
+    let parameters = foreground_only(foreground::Mode::MarqueeSolidFixed);
+    check_golden("fg_marquee_solid_fixed", parameters, &triggers);
+}
+
+#[test]
+fn fg_marquee_fade() {
+    let triggers = [(16, trigger_params(trigger::Mode::Foreground))];
+    check_golden(
+        "fg_marquee_fade",
+        foreground_only(foreground::Mode::MarqueeFade),
+        &triggers,
+    );
+}
+
+#[test]
+fn fg_marquee_fade_fixed() {
+    let triggers = [(16, trigger_params(trigger::Mode::Foreground))];
+    let parameters = foreground_only(foreground::Mode::MarqueeFadeFixed);
+    check_golden("fg_marquee_fade_fixed", parameters, &triggers);
+}
+
+#[test]
+fn fg_vu_meter() {
+    let parameters = AnimationParameters {
+        bg: bg(background::Mode::FillRainbow, c::R_VU_METER),
+        fg: fg(foreground::Mode::VUMeter, c::R_VU_METER),
+        trigger: da::TRIGGER_OFF,
+    };
+    check_golden("fg_vu_meter", parameters, &[]);
+}
+
+#[test]
+fn trigger_color_pulse() {
+    check_trigger("trigger_color_pulse", trigger::Mode::ColorPulse);
+}
+
+#[test]
+fn trigger_color_pulse_fade() {
+    check_trigger("trigger_color_pulse_fade", trigger::Mode::ColorPulseFade);
+}
+
+#[test]
+fn trigger_color_pulse_rainbow() {
+    check_trigger(
+        "trigger_color_pulse_rainbow",
+        trigger::Mode::ColorPulseRainbow,
+    );
+}
+
+#[test]
+fn trigger_color_shot() {
+    check_trigger("trigger_color_shot", trigger::Mode::ColorShot);
+}
+
+#[test]
+fn trigger_color_shot_fade() {
+    check_trigger("trigger_color_shot_fade", trigger::Mode::ColorShotFade);
+}
+
+#[test]
+fn trigger_color_shot_rainbow() {
+    check_trigger(
+        "trigger_color_shot_rainbow",
+        trigger::Mode::ColorShotRainbow,
+    );
+}
+
+#[test]
+fn trigger_flash() {
+    check_trigger("trigger_flash", trigger::Mode::Flash);
+}
+
+#[test]
+fn trigger_flash_fade() {
+    check_trigger("trigger_flash_fade", trigger::Mode::FlashFade);
+}
+
+#[test]
+fn trigger_flash_rainbow() {
+    check_trigger("trigger_flash_rainbow", trigger::Mode::FlashRainbow);
+}
+
+#[test]
+fn trigger_color_pulse_add() {
+    // the pulses add their light to the rotating rainbow instead of painting over it:
+    let (mut parameters, triggers) = trigger_over_background(trigger::Mode::ColorPulse);
+    parameters.bg = bg(background::Mode::FillRainbowRotate, c::R_ROYGBIV);
+    parameters.trigger.blend = c::LayerBlend::new(c::BlendMode::Add, 255);
+    check_golden("trigger_color_pulse_add", parameters, &triggers);
+}
+
+#[test]
+fn fg_marquee_overlay() {
+    let parameters = AnimationParameters {
+        bg: bg(background::Mode::FillRainbow, c::R_ROYGBIV),
+        fg: foreground::Parameters {
+            blend: c::LayerBlend::new(c::BlendMode::Normal, 128),
+            ..fg(foreground::Mode::MarqueeSolid, c::R_ON)
+        },
+        trigger: da::TRIGGER_OFF,
+    };
+    check_golden("fg_marquee_overlay", parameters, &[]);
+}
+
+#[test]
+fn stack_two_backgrounds() {
+    // a rainbow rotating each way, the second one blended half over the first:
+    let forward = bg(background::Mode::FillRainbowRotate, c::R_ROYGBIV);
+    let backward = background::Parameters {
+        direction: Direction::Negative,
+        blend: c::LayerBlend::new(c::BlendMode::Normal, 128),
+        ..bg(background::Mode::FillRainbowRotate, c::R_RB)
+    };
+    let layers = [
+        LayerParameters::Background(forward),
+        LayerParameters::Background(backward),
+    ];
+    compare_golden("stack_two_backgrounds", &render_stack(layers, |_, _| {}));
+}
+
+#[test]
+fn stack_two_marquees() {
+    // two marquees at different speeds, with only the second one triggered to its next color:
+    let slow = fg(foreground::Mode::MarqueeSolid, c::R_ROYGBIV);
+    let fast = foreground::Parameters {
+        step_time_ns: 50_000_000,
+        pixels_per_pixel_group: 1,
+        is_rainbow_forward: false,
+        blend: c::LayerBlend::new(c::BlendMode::Add, 255),
+        ..fg(foreground::Mode::MarqueeSolid, c::R_ROYGBIV)
+    };
+    let layers = [
+        LayerParameters::Background(da::BG_OFF),
+        LayerParameters::Foreground(slow),
+        LayerParameters::Foreground(fast),
+    ];
+    let rendered = render_stack(layers, |lc, frame| {
+        if frame == 16 {
+            lc.trigger_layer(0, 2, &trigger_params(trigger::Mode::Foreground));
+        }
+    });
+    compare_golden("stack_two_marquees", &rendered);
+}
+
+#[test]
+fn trigger_flash_fade_first_half() {
+    let (mut parameters, triggers) = trigger_over_background(trigger::Mode::FlashFade);
+    parameters.trigger.mask = Mask::Range {
+        start: 0,
+        end: NUM_LEDS / 2,
+    };
+    check_golden("trigger_flash_fade_first_half", parameters, &triggers);
+}
+
+#[test]
+fn fg_marquee_faded_edges() {
+    let parameters = AnimationParameters {
+        bg: da::BG_OFF,
+        fg: foreground::Parameters {
+            mask: Mask::Gradient(&[0, 255, 255, 0]),
+            ..fg(foreground::Mode::MarqueeSolid, c::R_ON)
+        },
+        trigger: da::TRIGGER_OFF,
+    };
+    check_golden("fg_marquee_faded_edges", parameters, &[]);
+}
+
+#[test]
+fn stack_every_third_led() {
+    let sparkle = background::Parameters {
+        mask: Mask::EveryNth { every: 3, start: 1 },
+        ..bg(background::Mode::Solid, c::R_ON)
+    };
+    let layers = [
+        LayerParameters::Background(bg(background::Mode::FillRainbowRotate, c::R_ROYGBIV)),
+        LayerParameters::Background(sparkle),
+    ];
+    compare_golden("stack_every_third_led", &render_stack(layers, |_, _| {}));
+}
+
+#[test]
+fn stack_luminance_mask() {
+    // the red only shows where the rotating blue and white pattern below it is bright:
+    let red = background::Parameters {
+        mask: Mask::Luminance(0),
+        ..bg(background::Mode::Solid, c::R_RED)
+    };
+    let layers = [
+        LayerParameters::Background(bg(background::Mode::FillRainbowRotate, c::R_BW)),
+        LayerParameters::Background(red),
+    ];
+    compare_golden("stack_luminance_mask", &render_stack(layers, |_, _| {}));
+}
+
+#[test]
+#[should_panic(expected = "Luminance masks have to follow a layer in the stack")]
+fn luminance_masks_follow_a_layer_in_the_stack() {
+    let red = background::Parameters {
+        mask: Mask::Luminance(2),
+        ..bg(background::Mode::Solid, c::R_RED)
+    };
+    let layers = [
+        LayerParameters::Background(bg(background::Mode::Solid, c::R_ON)),
+        LayerParameters::Background(red),
+    ];
+    let translation_array = utility::default_translation_array::<NUM_LEDS>(0);
+    let _ = Animation::new_stack_with_seed(layers, translation_array, FRAME_RATE_HZ.Hz(), SEED);
+}
+
+#[test]
+fn stacks_have_at_most_one_trigger_layer() {
+    let layers = [
+        LayerParameters::Background(da::BG_OFF),
+        LayerParameters::Trigger(da::TRIGGER_OFF),
+        LayerParameters::Trigger(da::TRIGGER_OFF),
+    ];
+    let translation_array = utility::default_translation_array::<NUM_LEDS>(0);
+    let animation =
+        Animation::new_stack_with_seed(layers, translation_array, FRAME_RATE_HZ.Hz(), SEED);
+    assert!(matches!(animation, Err(StackError::TooManyTriggerLayers)));
+}
+
+#[test]
+fn transition_crossfade() {
+    compare_golden(
+        "transition_crossfade",
+        &render_transition(Transition::Crossfade),
+    );
+}
+
+#[test]
+fn transition_wipe_negative() {
+    let rendered = render_transition(Transition::Wipe(Direction::Negative));
+    compare_golden("transition_wipe_negative", &rendered);
+}
+
+#[test]
+fn transition_dissolve() {
+    compare_golden(
+        "transition_dissolve",
+        &render_transition(Transition::Dissolve),
+    );
+}
+
+#[test]
+fn transition_fade_through_black() {
+    let rendered = render_transition(Transition::FadeThroughBlack);
+    compare_golden("transition_fade_through_black", &rendered);
+}
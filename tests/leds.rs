@@ -0,0 +1,416 @@
+//! Checks how a `LogicalStrip` turns colors into the bytes each strip is sent: RGBW white
+//! extraction, gamma, color correction, dithering, 16 bit colors, brightness and power limits.
+
+use bl602_ws2811::*;
+
+use colors::{self as c, Color, Color16};
+use hardware::{DynamicPin, HardwareController};
+use leds::ws28xx as strip;
+use simulator::VirtualClock;
+use waveform::{decode, verify_strip, RecordingPin};
+
+/// Owns the buffers a `LogicalStrip` borrows, sized to fit its strips.
+struct Fixture {
+    strips: Vec<strip::PhysicalStrip>,
+    bytes: Vec<u8>,
+    colors: Vec<Color16>,
+    carries: Vec<u8>,
+}
+
+impl Fixture {
+    fn new(strips: impl IntoIterator<Item = strip::PhysicalStrip>) -> Self {
+        let strips: Vec<_> = strips.into_iter().collect();
+        let (leds, bytes) = (get_total_num_leds(&strips), get_total_num_bytes(&strips));
+        Self {
+            strips,
+            bytes: vec![0; bytes],
+            colors: vec![Color16::default(); leds],
+            carries: vec![0; bytes],
+        }
+    }
+
+    fn logical_strip(&mut self) -> strip::LogicalStrip<'_> {
+        strip::LogicalStrip::new_with_dithering(
+            &mut self.bytes,
+            &mut self.colors,
+            &mut self.strips,
+            &mut self.carries,
+        )
+    }
+}
+
+const TIMINGS: strip::StripTimings = strip::StripTimings::WS2812_ADAFRUIT;
+const NUM_LEDS: usize = 7;
+
+// three GRB LEDs, then four reversed BRG ones:
+fn two_strips() -> [strip::PhysicalStrip; 2] {
+    [
+        strip::PhysicalStrip::new(3, strip::ColorOrder::GRB, TIMINGS),
+        strip::PhysicalStrip {
+            reversed: true,
+            ..strip::PhysicalStrip::new(4, strip::ColorOrder::BRG, TIMINGS)
+        },
+    ]
+}
+
+fn set_all(logical_strip: &mut strip::LogicalStrip, color: Color) {
+    for index in 0..NUM_LEDS {
+        logical_strip.set_color_at_index(index, color);
+    }
+}
+
+// rounds an 8.8 fixed point gamma table value to the byte sent without dithering:
+fn round(level: u16) -> u8 {
+    ((level + 128) >> 8) as u8
+}
+
+#[test]
+fn rgb_and_rgbw_strips_share_a_logical_strip() {
+    const WARM_WHITE: Color = Color {
+        r: 255,
+        g: 200,
+        b: 150,
+    };
+    let mut fixture = Fixture::new([
+        strip::PhysicalStrip {
+            pixel_format: strip::PixelFormat::Rgbw(c::WhiteExtraction::Min),
+            ..strip::PhysicalStrip::new(2, strip::ColorOrder::GRB, TIMINGS)
+        },
+        strip::PhysicalStrip::new(3, strip::ColorOrder::BRG, TIMINGS),
+        strip::PhysicalStrip {
+            reversed: true,
+            pixel_format: strip::PixelFormat::Rgbw(c::WhiteExtraction::WhitePoint(WARM_WHITE)),
+            ..strip::PhysicalStrip::new(2, strip::ColorOrder::GRB, TIMINGS)
+        },
+    ]);
+    assert_eq!(fixture.bytes.len(), 2 * 4 + 3 * 3 + 2 * 4);
+    let mut logical_strip = fixture.logical_strip();
+    let colors = [
+        Color::new(200, 100, 50),
+        c::C_WHITE,
+        c::C_RED,
+        c::C_GREEN,
+        c::C_BLUE,
+        WARM_WHITE,
+        c::C_RED,
+    ];
+    for (index, color) in colors.iter().enumerate() {
+        logical_strip.set_color_at_index(index, *color);
+    }
+
+    let g = |v: u8| c::GAMMA8[v as usize];
+    // GRBW, with the shared part of each color moved onto the white channel:
+    assert_eq!(
+        logical_strip.strip_bytes(0),
+        [g(50), g(150), g(0), g(50), 0, 0, 0, g(255)]
+    );
+    assert_eq!(logical_strip.strip_bytes(1).len(), 9);
+    // reversed, so red comes first, then the warm white which the white LED makes on its own:
+    assert_eq!(
+        logical_strip.strip_bytes(2),
+        [0, g(255), 0, 0, 0, 0, 0, g(255)]
+    );
+
+    // and the 4 byte LEDs go out on the wire as they are:
+    const MAX_BYTES: usize = 9;
+    let clock = VirtualClock::new();
+    let mut pins = [(); 3].map(|_| RecordingPin::<{ MAX_BYTES * 16 + 1 }>::new(&clock, 0));
+    {
+        let [pin_0, pin_1, pin_2] = &mut pins;
+        let mut pins: [DynamicPin; 3] = [pin_0, pin_1, pin_2];
+        let mut hc = HardwareController::new(&mut pins, clock.free_running_timer());
+        logical_strip.send_all_sequential(&mut hc);
+    }
+    for (strip_index, pin) in pins.iter().enumerate() {
+        let report = decode::<MAX_BYTES>(pin.edges(), &TIMINGS);
+        assert!(
+            report.is_in_spec(),
+            "strip {}: {:?}",
+            strip_index,
+            report.violations
+        );
+        assert_eq!(verify_strip(&report, &logical_strip, strip_index), Ok(()));
+    }
+}
+
+#[test]
+fn each_strip_uses_its_own_gamma_curve() {
+    const BATCH_B: c::GammaCurve = c::GammaCurve {
+        r: &c::gamma_table(2.2),
+        g: &c::gamma_table(2.6),
+        b: &c::LINEAR,
+        w: &c::LINEAR,
+    };
+    let mut strips = two_strips();
+    strips[1].gamma = BATCH_B;
+    let mut fixture = Fixture::new(strips);
+    let mut logical_strip = fixture.logical_strip();
+    set_all(&mut logical_strip, Color::new(128, 128, 128));
+
+    // GRB with the Adafruit table on every channel:
+    assert_eq!(logical_strip.strip_bytes(0)[..3], [c::GAMMA8[128]; 3]);
+    // BRG, 128/255 raised to the power of each channel's gamma:
+    assert_eq!(logical_strip.strip_bytes(1)[..3], [128, 56, 42]);
+
+    logical_strip.set_gamma(0, c::GammaCurve::CIE_LIGHTNESS);
+    assert_eq!(
+        logical_strip.strip_bytes(0)[..3],
+        [round(c::CIE_LIGHTNESS[128]); 3]
+    );
+    assert_eq!(c::LINEAR[200], 200 << 8);
+    assert_eq!(c::CIE_LIGHTNESS[255], 255 << 8);
+}
+
+#[test]
+fn color_correction_balances_white_per_strip() {
+    let mut strips = two_strips();
+    strips[0].gamma = c::GammaCurve::LINEAR;
+    strips[1].gamma = c::GammaCurve::LINEAR;
+    strips[1].correction = c::ColorCorrection::new(c::CORRECTION_TYPICAL_LED_STRIP, c::UNCORRECTED);
+    let mut fixture = Fixture::new(strips);
+    let mut logical_strip = fixture.logical_strip();
+    set_all(&mut logical_strip, c::C_WHITE);
+
+    // GRB, uncorrected:
+    assert_eq!(logical_strip.strip_bytes(0)[..3], [255, 255, 255]);
+    // BRG, with green and blue turned down:
+    assert_eq!(logical_strip.strip_bytes(1)[..3], [240, 255, 176]);
+
+    let halogen = c::ColorCorrection::new(c::CORRECTION_TYPICAL_LED_STRIP, c::TEMPERATURE_HALOGEN);
+    logical_strip.set_correction(1, halogen);
+    assert_eq!(halogen.factors(), [255, 166, 210]);
+    assert_eq!(logical_strip.strip_bytes(1)[..3], [210, 255, 166]);
+}
+
+#[test]
+fn dithering_shows_levels_between_led_values() {
+    const DIM: Color = Color { r: 20, g: 0, b: 0 };
+    const FRAMES: usize = 64;
+    let mut strips = two_strips();
+    strips[0].dithering = strip::Dithering::Ordered;
+    strips[1].dithering = strip::Dithering::ErrorCarry;
+    let mut fixture = Fixture::new(strips);
+    let mut logical_strip = fixture.logical_strip();
+
+    // 20 is well below FIRST_NON_OFF_COLOR, so it rounds to off without dithering:
+    let level = c::ADAFRUIT_GAMMA[DIM.r as usize];
+    assert!(level > 0 && round(level) == 0);
+
+    let mut totals = [0; 2];
+    for _ in 0..FRAMES {
+        logical_strip.next_frame();
+        set_all(&mut logical_strip, DIM);
+        // red is the second byte of the first LED on both strips, GRB and BRG:
+        for (total, strip_index) in totals.iter_mut().zip(0..) {
+            *total += logical_strip.strip_bytes(strip_index)[1] as u32;
+        }
+    }
+
+    // on average over the frames both come out between off and 1, the ordered pattern to the
+    // nearest eighth and the carried error exactly:
+    let expected = level as u32 * FRAMES as u32 / 256;
+    let [ordered, carried] = totals;
+    assert!(ordered > 0 && ordered.abs_diff(expected) <= FRAMES as u32 / 8);
+    assert!(
+        carried.abs_diff(expected) <= 1,
+        "{} != {}",
+        carried,
+        expected
+    );
+}
+
+// a strip of linear, error carrying LEDs, where a 16 bit color is sent as its 8.8 level:
+fn error_carry_strips() -> [strip::PhysicalStrip; 2] {
+    let mut strips = two_strips();
+    strips[0].gamma = c::GammaCurve::LINEAR;
+    strips[0].dithering = strip::Dithering::ErrorCarry;
+    strips
+}
+
+#[test]
+fn wide_colors_are_only_rounded_by_the_strip() {
+    const FRAMES: u32 = 64;
+    // half way between 100 and 101, which an 8 bit fade would have truncated to 100:
+    let color = c::Color16::new(100 * 257 + 128, 0, 0);
+    let mut fixture = Fixture::new(error_carry_strips());
+    let mut logical_strip = fixture.logical_strip();
+
+    let mut total = 0;
+    for _ in 0..FRAMES {
+        logical_strip.next_frame();
+        logical_strip.set_color16_at_index(0, color);
+        // GRB, so red is the second byte:
+        total += logical_strip.strip_bytes(0)[1] as u32;
+    }
+    assert!(total.abs_diff(FRAMES * 201 / 2) <= 1, "{}", total);
+    assert_eq!(logical_strip.get_color_at_index(0).r, 100);
+
+    // widening and rounding back gives the same color, so 8 bit colors come out as they did:
+    let orange = c::Color16::from(c::C_ORANGE);
+    assert_eq!(orange.to_color().r, c::C_ORANGE.r);
+    assert_eq!(orange.to_color().g, c::C_ORANGE.g);
+    assert_eq!(
+        c::Color16::from(c::C_WHITE),
+        c::Color16::new(65535, 65535, 65535)
+    );
+}
+
+#[test]
+fn error_carry_carries_once_a_frame() {
+    const FRAMES: u32 = 64;
+    let color = c::Color16::new(100 * 257 + 128, 0, 0);
+    let mut fixture = Fixture::new(error_carry_strips());
+    let mut logical_strip = fixture.logical_strip();
+
+    // set twice a frame, like overlapping animations or a transition would:
+    let mut total = 0;
+    for _ in 0..FRAMES {
+        logical_strip.next_frame();
+        logical_strip.set_color16_at_index(0, color);
+        logical_strip.set_color16_at_index(0, color);
+        total += logical_strip.strip_bytes(0)[1] as u32;
+    }
+    assert!(total.abs_diff(FRAMES * 201 / 2) <= 1, "{}", total);
+
+    // and not set again, where it keeps dithering the color it was last set to:
+    let mut total = 0;
+    for _ in 0..FRAMES {
+        logical_strip.next_frame();
+        total += logical_strip.strip_bytes(0)[1] as u32;
+    }
+    assert!(total.abs_diff(FRAMES * 201 / 2) <= 1, "{}", total);
+}
+
+#[test]
+fn changing_settings_keeps_wide_colors() {
+    // a grey a quarter of the way from 100 to 101, which the RGBW strip sends on its white
+    // channel:
+    let grey = c::Color16::new(100 * 257 + 64, 100 * 257 + 64, 100 * 257 + 64);
+
+    let encode = |settings_first: bool| {
+        let mut strips = two_strips();
+        strips[0].pixel_format = strip::PixelFormat::Rgbw(c::WhiteExtraction::Min);
+        strips[0].gamma = c::GammaCurve::LINEAR;
+        let mut fixture = Fixture::new(strips);
+        let mut logical_strip = fixture.logical_strip();
+        let set_colors = |logical_strip: &mut strip::LogicalStrip| {
+            (0..NUM_LEDS).for_each(|index| logical_strip.set_color16_at_index(index, grey));
+        };
+        if !settings_first {
+            set_colors(&mut logical_strip);
+        }
+        logical_strip.set_brightness(0, 200);
+        logical_strip.set_brightness(1, 200);
+        logical_strip.set_gamma(1, c::GammaCurve::CIE_LIGHTNESS);
+        if settings_first {
+            set_colors(&mut logical_strip);
+        }
+        assert_eq!(logical_strip.get_color16_at_index(0), grey);
+        logical_strip.bytes().to_vec()
+    };
+
+    // re-encoding after the settings change starts from the 16 bit colors, not rounded ones:
+    let bytes = encode(true);
+    assert_eq!(bytes, encode(false));
+    // dimmed to 78.6, where a grey of 100 would have gone to 78.4:
+    assert_eq!(bytes[..4], [0, 0, 0, 79]);
+}
+
+#[test]
+fn power_limits_turn_the_frame_down_to_fit() {
+    let mut strips = two_strips();
+    for strip in strips.iter_mut() {
+        strip.gamma = c::GammaCurve::LINEAR;
+    }
+    strips[0].power.max_ma = Some(93);
+    let mut fixture = Fixture::new(strips);
+    let mut logical_strip = fixture.logical_strip();
+    set_all(&mut logical_strip, c::C_WHITE);
+
+    // 20mA for each channel at full duty and 1mA for each LED:
+    assert_eq!(logical_strip.strip_current_ma(0), 3 * 3 * 20 + 3);
+    assert_eq!(logical_strip.strip_current_ma(1), 4 * 3 * 20 + 4);
+
+    // the first strip's own limit halves what its channels draw, the second has none:
+    logical_strip.limit_power();
+    assert_eq!(logical_strip.strip_bytes(0), [127; 9]);
+    assert_eq!(logical_strip.strip_bytes(1), [255; 12]);
+    assert!(logical_strip.strip_current_ma(0) <= 93);
+
+    // the global limit then turns both down by the same amount:
+    logical_strip.set_max_current(Some(200));
+    logical_strip.limit_power();
+    let estimate = logical_strip.estimated_current_ma();
+    assert!(estimate <= 200 && estimate > 190, "{}", estimate);
+    let [first, second] = [0, 1].map(|strip_index| logical_strip.strip_bytes(strip_index)[0]);
+    assert_eq!(second as u32 * 127 / 255, first as u32);
+
+    // without any limits a frame is left as it is:
+    logical_strip.set_max_current(None);
+    set_all(&mut logical_strip, c::C_WHITE);
+    logical_strip.limit_power();
+    assert_eq!(logical_strip.strip_bytes(1), [255; 12]);
+
+    // a strip whose idle draw is already over its limit is left dark rather than scaled:
+    let mut strips = two_strips();
+    strips[0].power.max_ma = Some(2);
+    let mut fixture = Fixture::new(strips);
+    let mut logical_strip = fixture.logical_strip();
+    set_all(&mut logical_strip, c::C_OFF);
+    logical_strip.set_max_current(Some(2));
+    logical_strip.limit_power();
+    assert_eq!(logical_strip.strip_bytes(0), [0; 9]);
+    assert_eq!(logical_strip.strip_current_ma(0), 3);
+}
+
+#[test]
+fn power_limits_dont_build_up_over_frames() {
+    let mut strips = two_strips();
+    strips[0].gamma = c::GammaCurve::LINEAR;
+    let mut fixture = Fixture::new(strips);
+    let mut logical_strip = fixture.logical_strip();
+    set_all(&mut logical_strip, c::C_WHITE);
+    logical_strip.set_max_current(Some(200));
+
+    // a frame held without setting its colors again is turned down the same amount each time:
+    logical_strip.limit_power();
+    let limited = logical_strip.bytes().to_vec();
+    assert!(limited.iter().all(|&byte| byte < 255), "{:?}", limited);
+    for _ in 0..5 {
+        logical_strip.next_frame();
+        logical_strip.limit_power();
+        assert_eq!(logical_strip.bytes(), limited);
+    }
+
+    // and comes back up once the limit is lifted:
+    logical_strip.set_max_current(None);
+    logical_strip.next_frame();
+    logical_strip.limit_power();
+    assert_eq!(logical_strip.bytes(), [255; 3 * 3 + 4 * 3]);
+}
+
+#[test]
+fn brightness_dims_before_the_gamma_curve() {
+    let mut strips = two_strips();
+    strips[0].gamma = c::GammaCurve::LINEAR;
+    let mut fixture = Fixture::new(strips);
+    let mut logical_strip = fixture.logical_strip();
+    set_all(&mut logical_strip, c::C_WHITE);
+
+    logical_strip.set_brightness(0, 128);
+    logical_strip.set_brightness(1, 128);
+    assert_eq!(logical_strip.strip_bytes(0)[..3], [128; 3]);
+    // the Adafruit curve sees the dimmed color, not a dimmed output:
+    assert_eq!(logical_strip.strip_bytes(1)[..3], [c::GAMMA8[128]; 3]);
+    // the colors the animations set are kept as they were:
+    assert_eq!(logical_strip.get_color_at_index(0).r, 255);
+
+    // the master brightness applies on top of each strip's own, including to colors already
+    // set:
+    logical_strip.set_master_brightness(u16::MAX / 2);
+    assert_eq!(logical_strip.strip_bytes(0)[..3], [64; 3]);
+    assert_eq!(logical_strip.strip_bytes(0)[6..], [64; 3]);
+
+    logical_strip.set_brightness(0, 0);
+    assert_eq!(logical_strip.strip_bytes(0), [0; 9]);
+}
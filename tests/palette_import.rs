@@ -0,0 +1,124 @@
+//! Checks the palette parsers against small examples of each format, filling the crate's own
+//! palette types the way firmware would at runtime.
+
+use bl602_ws2811::colors::{self as c, Color, GradientStop};
+use bl602_ws2811::palette_import::{self as import, ImportError, ImportedStop};
+
+fn rgb(color: Color) -> [u8; 3] {
+    [color.r, color.g, color.b]
+}
+
+#[test]
+fn fastled_bytes_and_source_agree() {
+    let bytes = [0, 255, 0, 0, 128, 0, 255, 0, 255, 0, 0, 255];
+    let source = "
+        0, 0xFF, 0, 0, // red
+        128, 0, 255, 0,
+        255, 0, 0, 0xff";
+
+    let mut from_bytes = [ImportedStop {
+        index: 0,
+        rgb: [0; 3],
+    }; 4];
+    let mut from_source = from_bytes;
+    assert_eq!(import::parse_fastled(&bytes, &mut from_bytes), Ok(3));
+    assert_eq!(
+        import::parse_fastled_source(source, &mut from_source),
+        Ok(3)
+    );
+    assert_eq!(from_bytes, from_source);
+}
+
+#[test]
+fn fastled_source_is_found_by_name() {
+    let source = "
+        // a comment before the palette
+        DEFINE_GRADIENT_PALETTE( fire_gp ) {
+            0, 0, 0, 0,
+            255, 255, 255, 0};
+        DEFINE_GRADIENT_PALETTE(ice_gp) { 0, 0, 0, 255, 255, 255, 255, 255 };";
+
+    let mut palettes = import::FastLedPalettes::new(source);
+    let (name, body) = palettes.next().unwrap();
+    assert_eq!(name, "fire_gp");
+
+    let mut stops = [GradientStop::default(); 2];
+    assert_eq!(import::parse_fastled_source(body, &mut stops), Ok(2));
+    assert_eq!(stops[1].position, 65535);
+    assert_eq!(rgb(stops[1].color), [255, 255, 0]);
+
+    assert_eq!(palettes.next().map(|(name, _)| name), Some("ice_gp"));
+    assert!(palettes.next().is_none());
+}
+
+#[test]
+fn fastled_errors() {
+    let mut stops = [GradientStop::default(); 2];
+    assert_eq!(
+        import::parse_fastled(&[], &mut stops),
+        Err(ImportError::Empty)
+    );
+    assert_eq!(
+        import::parse_fastled(&[0, 1, 2], &mut stops),
+        Err(ImportError::TruncatedStop)
+    );
+    assert_eq!(
+        import::parse_fastled(&[128, 0, 0, 0, 64, 0, 0, 0], &mut stops),
+        Err(ImportError::IndexOutOfOrder { entry: 1 })
+    );
+    assert_eq!(
+        import::parse_fastled(&[0, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0], &mut stops),
+        Err(ImportError::TooManyEntries { capacity: 2 })
+    );
+}
+
+#[test]
+fn wled_hex_and_numeric_forms() {
+    let hex = r##"{ "palette": [0, "ff8000", 255, "#0000ff"] }"##;
+    let numeric = r#"{"palette":[0,255,128,0,255,0,0,255]}"#;
+
+    let mut from_hex = [GradientStop::default(); 2];
+    let mut from_numeric = [GradientStop::default(); 2];
+    assert_eq!(import::parse_wled(hex, &mut from_hex), Ok(2));
+    assert_eq!(import::parse_wled(numeric, &mut from_numeric), Ok(2));
+    for (a, b) in from_hex.iter().zip(from_numeric.iter()) {
+        assert_eq!((a.position, rgb(a.color)), (b.position, rgb(b.color)));
+    }
+
+    assert_eq!(
+        import::parse_wled("{}", &mut from_hex),
+        Err(ImportError::MissingHeader)
+    );
+    let bad = r#"{"palette":[0,"ff80zz"]}"#;
+    assert_eq!(
+        import::parse_wled(bad, &mut from_hex),
+        Err(ImportError::BadValue { entry: 1 })
+    );
+}
+
+#[test]
+fn gpl_colors() {
+    let gpl = "GIMP Palette\nName: Test\nColumns: 2\n#\n255   0   0\tRed\n  0 128 255 Sky: blue\n";
+    let mut colors = [Color::default(); 4];
+    assert_eq!(import::parse_gpl(gpl, &mut colors), Ok(2));
+    assert_eq!(rgb(colors[1]), [0, 128, 255]);
+
+    let missing_header = "255 0 0 Red\n";
+    assert_eq!(
+        import::parse_gpl(missing_header, &mut colors),
+        Err(ImportError::MissingHeader)
+    );
+    let short_line = "GIMP Palette\n255 0\n";
+    assert_eq!(
+        import::parse_gpl(short_line, &mut colors),
+        Err(ImportError::BadValue { entry: 1 })
+    );
+}
+
+#[test]
+fn imported_palettes_follow_the_rainbows() {
+    assert_eq!(c::NUM_PALETTES, c::NUM_RAINBOWS + c::NUM_IMPORTED_PALETTES);
+    for (i, palette) in c::IMPORTED_PALETTES.iter().enumerate() {
+        assert_eq!(c::PALETTE_ARRAY[c::NUM_RAINBOWS + i].len(), palette.len());
+    }
+}
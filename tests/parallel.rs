@@ -0,0 +1,176 @@
+//! Transposes strips into bit-planes and sends them all at once on recording pins, checking each
+//! pin's waveform on its own.
+
+use bl602_ws2811::*;
+
+use colors::{self as c, Color16};
+use hardware::{DynamicPin, HardwareController, LedDriver, PortWrite};
+use leds::ws28xx as strip;
+use parallel::{bit_planes, transpose, BitPlane, ParallelController};
+use simulator::VirtualClock;
+use waveform::{decode, verify_strip, RecordingPin};
+
+const fn strip_of(led_count: usize) -> strip::PhysicalStrip {
+    strip::PhysicalStrip::new(
+        led_count,
+        strip::ColorOrder::GRB,
+        strip::StripTimings::WS2812_ADAFRUIT,
+    )
+}
+
+const STRIPS: [strip::PhysicalStrip; 3] = [strip_of(3), strip_of(5), strip_of(1)];
+const NUM_LEDS: usize = get_total_num_leds(&STRIPS);
+const NUM_BYTES: usize = get_total_num_bytes(&STRIPS);
+// one rising and one falling edge per bit of the longest strip, plus the reset:
+const NUM_EDGES: usize = 5 * 3 * 8 * 2 + 1;
+
+#[test]
+fn transpose_puts_each_strip_on_its_own_bit() {
+    let strips: [&[u8]; 3] = [&[0b1000_0001], &[0xFF], &[0x00]];
+    let planes = transpose(&strips, 0);
+    let ones: Vec<u32> = planes.iter().map(|plane| plane.ones).collect();
+    assert_eq!(
+        ones,
+        [0b011, 0b010, 0b010, 0b010, 0b010, 0b010, 0b010, 0b011]
+    );
+    assert!(planes.iter().all(|plane| plane.active == 0b111));
+}
+
+#[test]
+fn short_strips_drop_out_of_the_planes() {
+    let strips: [&[u8]; 3] = [&[0xFF, 0xFF], &[0xFF], &[]];
+    let planes: Vec<BitPlane> = bit_planes(&strips).collect();
+    assert_eq!(planes.len(), 16);
+    assert!(planes[..8].iter().all(|plane| *plane
+        == BitPlane {
+            active: 0b011,
+            ones: 0b011
+        }));
+    assert!(planes[8..].iter().all(|plane| *plane
+        == BitPlane {
+            active: 0b001,
+            ones: 0b001
+        }));
+}
+
+#[test]
+fn parallel_frames_take_as_long_as_the_longest_strip() {
+    let mut byte_buffer = [0; NUM_BYTES];
+    let mut color_buffer = [Color16::default(); NUM_LEDS];
+    let mut strips = STRIPS;
+    let mut logical_strip =
+        strip::LogicalStrip::new(&mut byte_buffer, &mut color_buffer, &mut strips);
+    let colors = [
+        c::C_RED,
+        c::C_GREEN,
+        c::C_BLUE,
+        c::C_ORANGE,
+        c::C_WHITE,
+        c::C_DEEP_BLUE,
+    ];
+    for index in 0..NUM_LEDS {
+        logical_strip.set_color_at_index(index, colors[index % colors.len()]);
+    }
+
+    let clock = VirtualClock::new();
+    let mut pins = [(); 3].map(|_| RecordingPin::<NUM_EDGES>::new(&clock, 0));
+    {
+        let [pin_0, pin_1, pin_2] = &mut pins;
+        let mut pins: [DynamicPin; 3] = [pin_0, pin_1, pin_2];
+        let hc = HardwareController::new(&mut pins, clock.free_running_timer());
+        ParallelController::new(hc).send_frame(&logical_strip);
+    }
+
+    let timings = &STRIPS[0].strip_timings;
+    let bit_ns = (timings.full_cycle / 3 * 3) as u64;
+    let reset_ns = strip::WS2811_DELAY_LOOPS_BEFORE_SEND as u64 * bit_ns / 3;
+    assert_eq!(clock.now(), reset_ns + 5 * 3 * 8 * bit_ns);
+    for (strip_index, (strip, pin)) in STRIPS.iter().zip(&pins).enumerate() {
+        assert!(!pin.has_overflowed());
+        let report = decode::<NUM_BYTES>(pin.edges(), timings);
+        assert!(
+            report.is_in_spec(),
+            "strip {}: {:?}",
+            strip_index,
+            report.violations
+        );
+        assert_eq!(report.bit_count, strip.led_count * 3 * 8);
+        assert_eq!(verify_strip(&report, &logical_strip, strip_index), Ok(()));
+    }
+}
+
+#[test]
+fn a_frame_without_strips_sends_nothing() {
+    let mut strips: [strip::PhysicalStrip; 0] = [];
+    let logical_strip = strip::LogicalStrip::new(&mut [], &mut [], &mut strips);
+    let clock = VirtualClock::new();
+    let mut pins: [DynamicPin; 0] = [];
+    let hc = HardwareController::new(&mut pins, clock.free_running_timer());
+    ParallelController::new(hc).send_frame(&logical_strip);
+    assert_eq!(clock.now(), 0);
+}
+
+#[test]
+fn clocked_strips_are_left_out_of_parallel_frames() {
+    let mut strips = STRIPS;
+    strips[1].protocol = strip::Protocol::Ws2801 {
+        clock_hz: 1_000_000,
+    };
+    let mut byte_buffer = [0; NUM_BYTES];
+    let mut color_buffer = [Color16::default(); NUM_LEDS];
+    let mut logical_strip =
+        strip::LogicalStrip::new(&mut byte_buffer, &mut color_buffer, &mut strips);
+    for index in 0..NUM_LEDS {
+        logical_strip.set_color_at_index(index, c::C_WHITE);
+    }
+
+    let clock = VirtualClock::new();
+    let mut pins = [(); 3].map(|_| RecordingPin::<NUM_EDGES>::new(&clock, 0));
+    {
+        let [pin_0, pin_1, pin_2] = &mut pins;
+        let mut pins: [DynamicPin; 3] = [pin_0, pin_1, pin_2];
+        let hc = HardwareController::new(&mut pins, clock.free_running_timer());
+        ParallelController::new(hc).send_frame(&logical_strip);
+    }
+
+    let timings = &STRIPS[0].strip_timings;
+    for strip_index in [0, 2] {
+        let report = decode::<NUM_BYTES>(pins[strip_index].edges(), timings);
+        assert!(
+            report.is_in_spec(),
+            "strip {}: {:?}",
+            strip_index,
+            report.violations
+        );
+        assert_eq!(verify_strip(&report, &logical_strip, strip_index), Ok(()));
+    }
+    assert!(pins[1].edges().is_empty());
+}
+
+#[test]
+fn strips_past_the_last_pin_are_skipped() {
+    const PINS: usize = parallel::MAX_PARALLEL_STRIPS + 8;
+    let clock = VirtualClock::new();
+    let mut recorders = [(); PINS].map(|_| RecordingPin::<{ 2 * 8 + 1 }>::new(&clock, 0));
+    {
+        let mut pins: Vec<DynamicPin> = recorders
+            .iter_mut()
+            .map(|pin| -> DynamicPin { pin })
+            .collect();
+        let mut hc = HardwareController::new(&mut pins, clock.free_running_timer());
+        // a mask only reaches the first 32 pins:
+        hc.set_high_mask(u32::MAX);
+        hc.set_low_mask(u32::MAX);
+
+        let strip_index = parallel::MAX_PARALLEL_STRIPS + 3;
+        ParallelController::new(hc).send_strip(strip_index, &strip_of(1), &[0xFF; 3]);
+    }
+    assert_eq!(clock.now(), 0);
+    for (pin_index, pin) in recorders.iter().enumerate() {
+        let edges = match pin_index < parallel::MAX_PARALLEL_STRIPS {
+            true => 2,
+            false => 0,
+        };
+        assert_eq!(pin.edges().len(), edges, "pin {}", pin_index);
+    }
+}
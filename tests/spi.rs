@@ -0,0 +1,207 @@
+//! Encodes `LogicalStrip` bytes with the SPI backend and checks the bitstream, both symbol by
+//! symbol and as the waveform the LEDs would see.
+
+use bl602_ws2811::*;
+
+use colors::{self as c, Color, Color16};
+use leds::ws28xx as strip;
+use spi::{SpiController, SpiEncoding, SpiError, SpiOutput};
+use waveform::{decode, verify_strip, Edge};
+
+const STRIPS: [strip::PhysicalStrip; 2] = [
+    strip::PhysicalStrip::new(
+        3,
+        strip::ColorOrder::GRB,
+        strip::StripTimings::WS2812_ADAFRUIT,
+    ),
+    strip::PhysicalStrip {
+        reversed: true,
+        ..strip::PhysicalStrip::new(
+            2,
+            strip::ColorOrder::BRG,
+            strip::StripTimings::WS2811_ADAFRUIT,
+        )
+    },
+];
+const NUM_LEDS: usize = get_total_num_leds(&STRIPS);
+const NUM_BYTES: usize = get_total_num_bytes(&STRIPS);
+
+// turns a bitstream into the edges the SPI data line would make, starting from a low line:
+fn to_edges(data: &[u8], clock_hz: u32) -> Vec<Edge> {
+    let bit_ns = 1_000_000_000.0 / clock_hz as f64;
+    let mut edges = vec![Edge {
+        time_ns: 0,
+        is_high: false,
+    }];
+    let bits = data
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |bit| (byte >> bit) & 1 == 1));
+    for (index, is_high) in bits.enumerate() {
+        let time_ns = (index as f64 * bit_ns).round() as u64;
+        edges.push(Edge { time_ns, is_high });
+    }
+    edges
+}
+
+#[test]
+fn symbols_come_from_the_strip_timings() {
+    let ws2812 = SpiEncoding::new(&strip::StripTimings::WS2812_ADAFRUIT, 3).unwrap();
+    assert_eq!(ws2812.clock_hz(), 2_400_000);
+    // 280us low for the reset, rounded up to whole bytes:
+    assert_eq!(ws2812.reset_len(), 84);
+    let mut out = [0xFF; 84 + 6];
+    // a zero is 100 and a one is 110:
+    assert_eq!(spi::encode(&ws2812, &[0x00, 0xFF], &mut out), Ok(84 + 6));
+    assert!(out[..84].iter().all(|&byte| byte == 0));
+    assert_eq!(
+        out[84..],
+        [
+            0b1001_0010,
+            0b0100_1001,
+            0b0010_0100,
+            0b1101_1011,
+            0b0110_1101,
+            0b1011_0110
+        ]
+    );
+
+    let ws2811 = SpiEncoding::new(&strip::StripTimings::WS2811_ADAFRUIT, 4).unwrap();
+    assert_eq!(ws2811.clock_hz(), 1_600_000);
+    assert_eq!(ws2811.reset_len(), 56);
+    let mut out = [0; 56 + 4];
+    // a zero is 1000 and a one is 1100:
+    assert_eq!(spi::encode(&ws2811, &[0xA0], &mut out), Ok(56 + 4));
+    assert_eq!(
+        out[56..],
+        [0b1100_1000, 0b1100_1000, 0b1000_1000, 0b1000_1000]
+    );
+
+    // a third of the WS2811's 2500ns cycle is too far from its 500ns zero:
+    let error = SpiEncoding::new(&strip::StripTimings::WS2811_ADAFRUIT, 3);
+    assert_eq!(
+        error,
+        Err(SpiError::HighOutOfSpec {
+            high_ns: 500,
+            nearest_ns: 833
+        })
+    );
+    let error = SpiEncoding::new(&strip::StripTimings::WS2812_ADAFRUIT, 5);
+    assert_eq!(error, Err(SpiError::UnsupportedSymbolLength { bits: 5 }));
+}
+
+#[test]
+fn decode_undoes_encode() {
+    let bytes: Vec<u8> = (0..=255).collect();
+    for bits_per_symbol in [3, 4] {
+        let encoding =
+            SpiEncoding::new(&strip::StripTimings::WS2812_ADAFRUIT, bits_per_symbol).unwrap();
+        let mut data = [0; 112 + 256 * 4];
+        let len = spi::encode(&encoding, &bytes, &mut data).unwrap();
+        let mut decoded = [0; 256];
+        assert_eq!(spi::decode(&encoding, &data[..len], &mut decoded), Ok(256));
+        assert_eq!(decoded[..], bytes[..]);
+
+        assert_eq!(
+            spi::decode(&encoding, &data[..len - 1], &mut decoded),
+            Err(SpiError::PartialByte)
+        );
+        let mut short = [0; 2];
+        let error = spi::encode(&encoding, &bytes, &mut short);
+        assert_eq!(error, Err(SpiError::TooManyBytes { capacity: 2 }));
+    }
+
+    let encoding = SpiEncoding::new(&strip::StripTimings::WS2812_ADAFRUIT, 3).unwrap();
+    let mut data = [0; 84 + 3];
+    // the second symbol is 111, which is neither a zero nor a one:
+    data[84..].copy_from_slice(&[0b1001_1110, 0b0100_1001, 0b0010_0100]);
+    let error = spi::decode(&encoding, &data, &mut [0; 1]);
+    assert_eq!(error, Err(SpiError::InvalidSymbol { bit_index: 1 }));
+    // and the reset has to be there, and all zeroes:
+    let error = spi::decode(&encoding, &data[1..], &mut [0; 1]);
+    assert_eq!(error, Err(SpiError::MissingReset));
+}
+
+// keeps a copy of everything written, one frame's worth per strip:
+#[derive(Default)]
+struct CaptureOutput {
+    writes: Vec<(usize, u32, Vec<u8>)>,
+    flushes: usize,
+}
+
+impl SpiOutput for &mut CaptureOutput {
+    fn write(&mut self, strip_index: usize, clock_hz: u32, data: &[u8]) {
+        self.writes.push((strip_index, clock_hz, data.to_vec()));
+    }
+
+    fn flush(&mut self) {
+        self.flushes += 1;
+    }
+}
+
+#[test]
+fn spi_backend_sends_waveforms_in_spec() {
+    let mut byte_buffer = [0; NUM_BYTES];
+    let mut color_buffer = [Color16::default(); NUM_LEDS];
+    let mut strips = STRIPS;
+    let mut logical_strip =
+        strip::LogicalStrip::new(&mut byte_buffer, &mut color_buffer, &mut strips);
+    let colors = [
+        c::C_RED,
+        c::C_ORANGE,
+        c::C_WHITE,
+        c::C_DEEP_BLUE,
+        Color::new(200, 100, 50),
+    ];
+    for (index, color) in colors.iter().enumerate() {
+        logical_strip.set_color_at_index(index, *color);
+    }
+
+    let encodings = [
+        SpiEncoding::new(&STRIPS[0].strip_timings, 3).unwrap(),
+        SpiEncoding::new(&STRIPS[1].strip_timings, 4).unwrap(),
+    ];
+    let mut output = CaptureOutput::default();
+    let mut buffer = [0; 84 + 9 * 4];
+    let strips = logical_strip.strips();
+    let mut driver = SpiController::new(&mut output, strips, &encodings, &mut buffer).unwrap();
+    logical_strip.send_all_sequential(&mut driver);
+
+    assert_eq!(output.flushes, 1);
+    assert_eq!(output.writes.len(), 2);
+    for (strip_index, clock_hz, data) in output.writes.iter() {
+        let timings = &STRIPS[*strip_index].strip_timings;
+        assert_eq!(*clock_hz, encodings[*strip_index].clock_hz());
+        let edges = to_edges(data, *clock_hz);
+        let report = decode::<NUM_BYTES>(&edges, timings);
+        assert!(
+            report.is_in_spec(),
+            "strip {}: {:?}",
+            strip_index,
+            report.violations
+        );
+        assert_eq!(verify_strip(&report, &logical_strip, *strip_index), Ok(()));
+    }
+}
+
+#[test]
+fn spi_buffers_are_checked_up_front() {
+    let encodings = [
+        SpiEncoding::new(&STRIPS[0].strip_timings, 3).unwrap(),
+        SpiEncoding::new(&STRIPS[1].strip_timings, 4).unwrap(),
+    ];
+    let mut output = CaptureOutput::default();
+
+    // the reset and 9 bytes of 3 bit symbols don't fit, so the first strip couldn't be sent:
+    let mut buffer = [0; 100];
+    let error = SpiController::new(&mut output, &STRIPS, &encodings, &mut buffer).err();
+    let expected = SpiError::BufferTooSmall {
+        strip_index: 0,
+        required: 84 + 9 * 3,
+    };
+    assert_eq!(error, Some(expected));
+
+    let mut buffer = [0; 84 + 9 * 3];
+    let error = SpiController::new(&mut output, &STRIPS, &encodings[..1], &mut buffer).err();
+    assert_eq!(error, Some(SpiError::MissingEncoding { strip_index: 1 }));
+    assert!(SpiController::new(&mut output, &STRIPS, &encodings, &mut buffer).is_ok());
+}
@@ -0,0 +1,159 @@
+//! Sends frames through `PhysicalStrip::send_bits` on recording pins and checks the resulting
+//! waveforms against each strip's timings and the `LogicalStrip` byte buffer.
+
+use bl602_ws2811::*;
+
+use colors::{self as c, Color, Color16};
+use hardware::{DynamicPin, HardwareController};
+use leds::ws28xx as strip;
+use simulator::VirtualClock;
+use waveform::{decode, received_colors, verify_strip, RecordingPin, Violation};
+
+const STRIPS: [strip::PhysicalStrip; 2] = [
+    strip::PhysicalStrip::new(
+        3,
+        strip::ColorOrder::GRB,
+        strip::StripTimings::WS2812_ADAFRUIT,
+    ),
+    strip::PhysicalStrip {
+        reversed: true,
+        ..strip::PhysicalStrip::new(
+            4,
+            strip::ColorOrder::BRG,
+            strip::StripTimings::WS2812_ADAFRUIT,
+        )
+    },
+];
+const NUM_LEDS: usize = 7;
+const NUM_BYTES: usize = NUM_LEDS * 3;
+// one rising and one falling edge per bit, plus the reset:
+const NUM_EDGES: usize = NUM_BYTES * 8 * 2 + 1;
+
+const TEST_COLORS: [Color; NUM_LEDS] = [
+    c::C_RED,
+    c::C_GREEN,
+    c::C_BLUE,
+    c::C_ORANGE,
+    c::C_WHITE,
+    c::C_DEEP_BLUE,
+    Color {
+        r: 200,
+        g: 100,
+        b: 50,
+    },
+];
+
+// rounds an 8.8 fixed point gamma table value to the byte sent without dithering:
+fn round(level: u16) -> u8 {
+    ((level + 128) >> 8) as u8
+}
+
+fn gamma(strip: &strip::PhysicalStrip, color: Color) -> Color {
+    let [r, g, b, _] = strip.gamma.apply([color.r, color.g, color.b, 0]);
+    Color::new(round(r), round(g), round(b))
+}
+
+#[test]
+fn send_bits_is_in_spec_and_matches_the_byte_buffer() {
+    let mut byte_buffer = [0; NUM_BYTES];
+    let mut color_buffer = [Color16::default(); NUM_LEDS];
+    let mut strips = STRIPS;
+    let mut logical_strip =
+        strip::LogicalStrip::new(&mut byte_buffer, &mut color_buffer, &mut strips);
+    for (index, color) in TEST_COLORS.iter().enumerate() {
+        logical_strip.set_color_at_index(index, *color);
+    }
+
+    let clock = VirtualClock::new();
+    let mut pin_0 = RecordingPin::<NUM_EDGES>::new(&clock, 0);
+    let mut pin_1 = RecordingPin::<NUM_EDGES>::new(&clock, 0);
+    {
+        let mut pins: [DynamicPin; 2] = [&mut pin_0, &mut pin_1];
+        let mut hc = HardwareController::new(&mut pins, clock.free_running_timer());
+        logical_strip.send_all_sequential(&mut hc);
+    }
+
+    let mut first_led = 0;
+    for (strip_index, (strip, pin)) in STRIPS.iter().zip([&pin_0, &pin_1]).enumerate() {
+        assert!(!pin.has_overflowed());
+        let report = decode::<NUM_BYTES>(pin.edges(), &strip.strip_timings);
+        assert!(
+            report.is_in_spec(),
+            "strip {}: {:?}",
+            strip_index,
+            report.violations
+        );
+        assert_eq!(verify_strip(&report, &logical_strip, strip_index), Ok(()));
+
+        let expected = TEST_COLORS[first_led..first_led + strip.led_count].iter();
+        for (received, expected) in received_colors(strip, &report.bytes).zip(expected) {
+            let expected = gamma(strip, *expected);
+            assert_eq!(
+                (received.r, received.g, received.b),
+                (expected.r, expected.g, expected.b)
+            );
+        }
+        first_led += strip.led_count;
+    }
+}
+
+#[test]
+fn slow_pin_writes_are_reported() {
+    let mut byte_buffer = [0; NUM_BYTES];
+    let mut color_buffer = [Color16::default(); NUM_LEDS];
+    let mut strips = STRIPS;
+    let mut logical_strip =
+        strip::LogicalStrip::new(&mut byte_buffer, &mut color_buffer, &mut strips);
+    logical_strip.set_color_at_index(0, c::C_WHITE);
+
+    let clock = VirtualClock::new();
+    let mut pin_0 = RecordingPin::<NUM_EDGES>::new(&clock, 600);
+    let mut pin_1 = RecordingPin::<NUM_EDGES>::new(&clock, 600);
+    {
+        let mut pins: [DynamicPin; 2] = [&mut pin_0, &mut pin_1];
+        let mut hc = HardwareController::new(&mut pins, clock.free_running_timer());
+        logical_strip.send_all_sequential(&mut hc);
+    }
+
+    let report = decode::<NUM_BYTES>(pin_0.edges(), &STRIPS[0].strip_timings);
+    assert!(!report.is_in_spec());
+    assert!(report
+        .violations
+        .iter()
+        .any(|v| matches!(v, Violation::PeriodOutOfSpec { .. })));
+}
+
+#[test]
+fn resets_are_checked_against_the_datasheet() {
+    let mut byte_buffer = [0; NUM_BYTES];
+    let mut color_buffer = [Color16::default(); NUM_LEDS];
+    let mut strips = STRIPS;
+    let logical_strip = strip::LogicalStrip::new(&mut byte_buffer, &mut color_buffer, &mut strips);
+
+    let clock = VirtualClock::new();
+    let mut pin_0 = RecordingPin::<NUM_EDGES>::new(&clock, 0);
+    let mut pin_1 = RecordingPin::<NUM_EDGES>::new(&clock, 0);
+    {
+        let mut pins: [DynamicPin; 2] = [&mut pin_0, &mut pin_1];
+        let mut hc = HardwareController::new(&mut pins, clock.free_running_timer());
+        logical_strip.send_all_sequential(&mut hc);
+    }
+
+    // send_bits waits 900 thirds of a bit, which is more than the 280us the strip needs:
+    let timings = &STRIPS[0].strip_timings;
+    let report = decode::<NUM_BYTES>(pin_0.edges(), timings);
+    assert!(report.is_in_spec(), "{:?}", report.violations);
+
+    // but not enough for a strip that needs a whole millisecond:
+    let slow_latch = strip::StripTimings {
+        reset: 1_000_000,
+        ..strip::StripTimings::WS2812_ADAFRUIT
+    };
+    let report = decode::<NUM_BYTES>(pin_0.edges(), &slow_latch);
+    let low_ns = strip::WS2811_DELAY_LOOPS_BEFORE_SEND as u64 * (timings.full_cycle / 3) as u64;
+    let expected = Violation::ResetTooShort {
+        low_ns,
+        required_ns: 1_000_000,
+    };
+    assert_eq!(report.violations[..], [expected]);
+}